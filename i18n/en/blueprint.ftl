@@ -19,7 +19,56 @@ view = View
 about = About
 file = File
 new-package = New package
+scan-system = Create from this system…
+scanning = Looking for installed packages…
+scan-desc = Packages you installed yourself, sorted into pages by their name. Pick the ones to keep in the new profile.
+nothing-found = No installed packages were found
+create-profile = Create profile
 edit-package = Edit package
+duplicate-package = Duplicate
+delete-package = Delete
+move-to = Move to
+package-copy = { $name } (copy)
+delete-package-title = Delete { $name }?
+delete-package-body = The package and its config files will be removed from this profile.
+delete = Delete
+disable-package-title = Disable { $name }?
+disable-package-body = The package is installed on this system. It can be removed now or kept installed and left out of future applies.
+remove-from-system = Remove from this system
+keep-installed = Keep installed
+edit = Edit
+undo = Undo
+duplicated-package = Duplicated { $name }
+moved-package = Moved { $name } to { $page }
+deleted-package = Deleted { $name }
+open = Open…
+save = Save
+save-as = Save as…
+blueprint-files = Blueprints
+
+# Profiles
+profile = Profile
+new-profile = New profile
+duplicate-profile = Duplicate profile
+edit-profile = Edit profile…
+delete-profile = Delete profile
+default-profile = Default
+untitled-profile = Untitled
+scanned-profile = This system
+profile-copy = { $name } (copy)
+no-profile = No profile selected
+
+# Variables
+variables = Variables
+variables-desc = Templated config files can use these as {"{{ name }}"}, along with user, home, hostname and distro.
+variable-name = email
+variable-default = Default
+variable-default-placeholder = Asked for when empty
+add-variable = Add variable
+no-variables = This profile declares no variables
+builtin-variables = Built-in variables
+missing-variables = Fill in { $names } before applying the blueprint.
+review-changes = Review changes
 
 # Context
 create = Create
@@ -27,3 +76,102 @@ package = Package
 name = Name
 enabled = Enabled
 description = Description
+no-package = No package selected
+page = Page
+source = Source
+package-manager = Package manager
+package-name = Package name
+flatpak-branch = Branch
+version = Version
+latest-version = Latest when empty
+features = Features
+features-placeholder = Comma-separated, such as pcre2
+git-repository = Git repository
+crates-io = Installed from crates.io when empty
+cargo-locked = Use the versions in the crate's Cargo.lock
+rustup-targets = Targets
+rustup-targets-placeholder = Comma-separated, such as wasm32-unknown-unknown
+rustup-components = Components
+rustup-components-placeholder = Comma-separated, such as rust-analyzer
+python-extras = Extra dependencies
+python-extras-placeholder = Comma-separated, such as requests or pandas>=2
+config-files = Config files
+config-file = Config file { $index }
+target = Target path
+extension = Extension
+file-mode = Permissions
+file-mode-placeholder = Octal mode, such as 644
+link-file = Link instead of copying
+link-linked = Linked
+link-missing = Not linked yet
+link-broken = Broken link
+link-replaced = Replaced by a regular file
+merge-file = Merge into the existing file
+template-file = Fill in {"{{ variables }}"}
+add-config-file = Add config file
+import-files = Import from disk…
+alternatives = Alternatives
+alternatives-desc = Sources to use on other distributions, by their os-release id
+distribution = Distribution
+add-alternative = Add alternative
+cancel = Cancel
+
+# Apply
+apply = Apply blueprint
+revert = Revert changes…
+plan = Review changes
+pending = Pending
+installing = Installing…
+removing = Removing…
+installed = Installed
+removed = Removed
+failed = Failed
+apply-finished = { $installed } installed, { $removed } removed, { $deployed } files written, { $restored } restored, { $failed } failed
+deployed-file = Written
+restored-file = Restored
+backup-file = Previous contents saved to { $path }
+install = Install
+skip = Already installed
+remove = Remove
+bootstrap-rustup = Install with the official installer from sh.rustup.rs
+files = Files
+create-file = Create
+overwrite-file = Overwrite
+unchanged-file = Unchanged
+invalid-file = Skipped, the file cannot be written
+invalid-package = Skipped, the package has errors
+restore-file = Restore the original
+delete-file = Delete
+approve-and-apply = Approve and apply
+missing = Missing
+outdated = Outdated ({ $installed })
+unavailable = Unavailable on this system
+drifted = Changed on disk
+drift = Changed config files
+no-drift = No config files were changed on disk
+blueprint = Blueprint
+on-disk = On disk
+pull-drift = Pull into blueprint
+overwrite-drift = Overwrite from blueprint
+
+# History
+history = History
+no-history = Blueprint has not changed anything on this system yet
+interrupted-run = { $time } (interrupted)
+exit-status = Exited with { $code }
+no-exit-status = Did not exit
+roll-back = Roll back
+
+# Validation
+invalid-name = The name cannot be empty
+invalid-source-name = The package name cannot be empty, start with a dash or contain spaces
+invalid-version = The version cannot start with a dash or contain spaces
+invalid-features = Features cannot be empty, start with a dash or contain spaces
+invalid-git = The git repository cannot start with a dash or contain spaces
+invalid-targets = Targets cannot be empty, start with a dash or contain spaces
+invalid-components = Components cannot be empty, start with a dash or contain spaces
+invalid-extras = Extra dependencies cannot be empty, start with a dash or contain spaces
+invalid-target = Config file { $index } needs a target path
+invalid-merge-format = Config file { $index } can only be merged if its extension is json, toml, ini, conf, yaml or yml
+invalid-alternative = The alternative for "{ $distro }" needs a distribution and a valid package name
+duplicate-alternative = There is more than one alternative for "{ $distro }"
@@ -1,19 +1,31 @@
 // SPDX-License-Identifier: GPL-3.0
 
 use crate::config::Config;
+use crate::engine::journal::{Journal, Record, Transaction};
+use crate::engine::plan::{self, Plan};
+use crate::engine::template;
+use crate::engine::{
+    self, detect::Installed, detect::Status, drift::Drift, host::HostInfo, Engine,
+};
 use crate::fl;
 use cosmic::app::{Command, Core};
-use cosmic::cosmic_config::{self, CosmicConfigEntry};
-use cosmic::iced::{Alignment, Subscription};
+use cosmic::cosmic_config;
+use cosmic::iced::{Alignment, Length, Subscription};
+use cosmic::prelude::CollectionWidget;
 use cosmic::widget::{self, icon, menu, nav_bar};
 use cosmic::{cosmic_theme, theme, Application, ApplicationExt, Element};
+use editor::PackageEditor;
 use futures_util::SinkExt;
-use models::package::{Package, Source};
+use models::blueprint::{self, Blueprint, Variable};
+use models::package::{ConfigFile, DeployMethod, Kind, Package};
+use models::run::{self, Run};
 use page::PageView;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 use uuid::Uuid;
 
+pub mod editor;
 pub mod models;
 pub mod page;
 
@@ -34,7 +46,34 @@ pub struct AppModel {
     config_handler: Option<cosmic_config::Config>,
     // Configuration data that persists between application runs.
     config: Config,
-    package: Option<Uuid>,
+    /// The form for the package being created or edited.
+    editor: Option<PackageEditor>,
+    /// The dialog waiting for the user to confirm an action, if any.
+    dialog: Option<Dialog>,
+    /// Package changes that can be undone, with a summary of each, newest last.
+    undo: Vec<(String, Vec<Package>)>,
+    /// The blueprint file the active profile was last opened from or saved to.
+    file: Option<PathBuf>,
+    /// Names of the profiles, for the profile switcher.
+    profile_names: Vec<String>,
+    /// The changes awaiting review before the blueprint is applied.
+    plan: Option<Plan>,
+    /// The blueprint currently being applied, if any.
+    run: Option<Run>,
+    /// What the package managers report as installed on this machine.
+    installed: Option<Installed>,
+    /// The distribution and package managers of this machine.
+    host: HostInfo,
+    /// Config files of the active profile that were changed on disk, by package.
+    drift: HashMap<Uuid, Vec<Drift>>,
+    /// The package whose changed config files are being reviewed.
+    drift_package: Option<Uuid>,
+    /// Packages found on this machine and whether to keep each, for a new profile.
+    scan: Vec<(Package, bool)>,
+    /// Whether the machine is being scanned for packages.
+    scanning: bool,
+    /// Past runs read from the journal, newest first.
+    history: Vec<Transaction>,
     shells: page::PageView,
     editors: page::PageView,
     languages: page::PageView,
@@ -49,12 +88,63 @@ pub enum Message {
     SubscriptionChannel,
     ToggleContextPage(ContextPage),
     UpdateConfig(Config),
+    Open,
+    Load(Option<PathBuf>),
+    Save,
+    SaveAs,
+    SaveTo(Option<PathBuf>),
+    SelectProfile(usize),
+    NewProfile,
+    DuplicateProfile,
+    DeleteProfile,
+    ProfileName(String),
+    ProfileDescription(String),
+    AddVariable,
+    RemoveVariable(usize),
+    VariableName(usize, String),
+    VariableDescription(usize, String),
+    VariableDefault(usize, String),
+    /// Sets the value of a variable on this machine.
+    VariableValue(String, String),
     NewPackage,
     EditPackage(Uuid),
+    Editor(editor::Message),
     Page(Page, page::Message),
-    PackageTitle(usize, String),
-    TogglePackage(usize, bool),
-    PackageDescription(usize, String),
+    TogglePackage(Uuid, bool),
+    /// Disables a package, removing it from this machine if asked to.
+    DisablePackage(Uuid, bool),
+    DuplicatePackage(Uuid),
+    MovePackage(Uuid, Page),
+    DeletePackage(Uuid),
+    ShowDrift(Uuid),
+    /// Config files changed on disk, by package.
+    DriftDetected(HashMap<Uuid, Vec<Drift>>),
+    /// Writes the blueprint's contents over the changed config file at an index.
+    OverwriteDrift(Uuid, usize),
+    /// Copies the changed config file at an index into the blueprint.
+    PullDrift(Uuid, usize),
+    ConfirmDialog,
+    CancelDialog,
+    Undo,
+    Plan,
+    ApproveStep(usize, bool),
+    ApproveFile(usize, bool),
+    Apply,
+    /// Plans removing what Blueprint installed and restoring the files it replaced.
+    Revert,
+    /// Reads past runs from the journal.
+    History,
+    HistoryLoaded(Vec<Transaction>),
+    /// Plans undoing one past run.
+    Rollback(Uuid),
+    Engine(engine::Event),
+    Detect,
+    Detected(Installed),
+    /// Looks for the packages installed on this machine to create a profile from.
+    Scan,
+    Scanned(Vec<Package>),
+    SelectScanned(usize, bool),
+    CreateFromScan,
 }
 
 /// Create a COSMIC application from the app model
@@ -69,7 +159,7 @@ impl Application for AppModel {
     type Message = Message;
 
     /// Unique identifier in RDNN (reverse domain name notation) format.
-    const APP_ID: &'static str = "dev.edfloreshz.Blueprint";
+    const APP_ID: &'static str = crate::config::APP_ID;
 
     fn core(&self) -> &Core {
         &self.core
@@ -110,18 +200,7 @@ impl Application for AppModel {
             .data::<Page>(Page::Tools)
             .icon(icon::from_name("applications-utilities-symbolic"));
 
-        let config = cosmic_config::Config::new(Self::APP_ID, Config::VERSION)
-            .map(|context| match Config::get_entry(&context) {
-                Ok(config) => config,
-                Err((errors, config)) => {
-                    for error in errors {
-                        log::error!("error loading app config: {}", error);
-                    }
-
-                    config
-                }
-            })
-            .unwrap_or_default();
+        let (config_handler, config) = Config::load();
 
         // Construct the app model with the runtime's core.
         let mut app = AppModel {
@@ -130,9 +209,22 @@ impl Application for AppModel {
             nav,
             key_binds: HashMap::new(),
             // Optional configuration file for an application.
-            config_handler: cosmic_config::Config::new(Self::APP_ID, Config::VERSION).ok(),
+            config_handler,
             config: config.clone(),
-            package: None,
+            editor: None,
+            dialog: None,
+            undo: vec![],
+            file: None,
+            profile_names: Self::profile_names(&config),
+            plan: None,
+            run: None,
+            installed: None,
+            host: HostInfo::detect(std::path::Path::new("/")),
+            drift: HashMap::new(),
+            drift_package: None,
+            scan: vec![],
+            scanning: false,
+            history: vec![],
             shells: PageView::new(Page::Shells, config.clone()),
             languages: PageView::new(Page::Languages, config.clone()),
             editors: PageView::new(Page::Editors, config.clone()),
@@ -140,8 +232,13 @@ impl Application for AppModel {
             tools: PageView::new(Page::Tools, config),
         };
 
-        // Create a startup command that sets the window title.
-        let command = app.update_title();
+        let host = app.host.clone();
+        for view in app.pages_mut() {
+            view.update(page::Message::Host(host.clone()));
+        }
+
+        // Create a startup command that sets the window title and checks what is installed.
+        let command = Command::batch(vec![app.update_title(), app.update(Message::Detect)]);
 
         (app, command)
     }
@@ -153,22 +250,57 @@ impl Application for AppModel {
                 menu::root(fl!("file")),
                 menu::items(
                     &self.key_binds,
-                    vec![menu::Item::Button(
-                        fl!("new-package"),
-                        MenuAction::NewPackage,
-                    )],
+                    vec![
+                        menu::Item::Button(fl!("new-package"), MenuAction::NewPackage),
+                        menu::Item::Button(fl!("scan-system"), MenuAction::Scan),
+                        menu::Item::Divider,
+                        menu::Item::Button(fl!("open"), MenuAction::Open),
+                        menu::Item::Button(fl!("save"), MenuAction::Save),
+                        menu::Item::Button(fl!("save-as"), MenuAction::SaveAs),
+                        menu::Item::Divider,
+                        menu::Item::Button(fl!("apply"), MenuAction::Apply),
+                        menu::Item::Button(fl!("revert"), MenuAction::Revert),
+                    ],
+                ),
+            ),
+            menu::Tree::with_children(
+                menu::root(fl!("edit")),
+                menu::items(
+                    &self.key_binds,
+                    vec![menu::Item::Button(fl!("undo"), MenuAction::Undo)],
+                ),
+            ),
+            menu::Tree::with_children(
+                menu::root(fl!("profile")),
+                menu::items(
+                    &self.key_binds,
+                    vec![
+                        menu::Item::Button(fl!("new-profile"), MenuAction::NewProfile),
+                        menu::Item::Button(fl!("duplicate-profile"), MenuAction::DuplicateProfile),
+                        menu::Item::Button(fl!("edit-profile"), MenuAction::EditProfile),
+                        menu::Item::Button(fl!("variables"), MenuAction::Variables),
+                    ],
                 ),
             ),
             menu::Tree::with_children(
                 menu::root(fl!("view")),
                 menu::items(
                     &self.key_binds,
-                    vec![menu::Item::Button(fl!("about"), MenuAction::About)],
+                    vec![
+                        menu::Item::Button(fl!("history"), MenuAction::History),
+                        menu::Item::Button(fl!("about"), MenuAction::About),
+                    ],
                 ),
             ),
         ]);
 
-        vec![menu_bar.into()]
+        let profiles = widget::dropdown(
+            &self.profile_names,
+            self.config.active_index(),
+            Message::SelectProfile,
+        );
+
+        vec![menu_bar.into(), profiles.into()]
     }
 
     /// Enables the COSMIC application to create a nav bar with this model.
@@ -184,28 +316,90 @@ impl Application for AppModel {
 
         Some(match self.context_page {
             ContextPage::About => self.about(),
-            ContextPage::NewPackage | ContextPage::EditPackage => self.package_view(),
+            ContextPage::NewPackage | ContextPage::EditPackage => match &self.editor {
+                Some(editor) => editor.view().map(Message::Editor),
+                None => widget::text(fl!("no-package")).into(),
+            },
+            ContextPage::Profile => self.profile_view(),
+            ContextPage::Plan => self.plan_view(),
+            ContextPage::Apply => self.run_view(),
+            ContextPage::Drift => self.drift_view(),
+            ContextPage::Variables => self.variables_view(),
+            ContextPage::Scan => self.scan_view(),
+            ContextPage::History => self.history_view(),
         })
     }
 
+    /// Display a dialog asking the user to confirm an action.
+    fn dialog(&self) -> Option<Element<Self::Message>> {
+        let dialog = match self.dialog.as_ref()? {
+            Dialog::DeletePackage(id) => {
+                let name = self
+                    .config
+                    .active_packages()
+                    .iter()
+                    .find(|package| package.id == *id)
+                    .map(|package| package.name.clone())
+                    .unwrap_or_default();
+
+                widget::dialog(fl!("delete-package-title", name = name))
+                    .body(fl!("delete-package-body"))
+                    .primary_action(
+                        widget::button::destructive(fl!("delete")).on_press(Message::ConfirmDialog),
+                    )
+                    .secondary_action(
+                        widget::button::standard(fl!("cancel")).on_press(Message::CancelDialog),
+                    )
+            }
+            Dialog::DisablePackage(id) => {
+                let name = self
+                    .config
+                    .active_packages()
+                    .iter()
+                    .find(|package| package.id == *id)
+                    .map(|package| package.name.clone())
+                    .unwrap_or_default();
+
+                widget::dialog(fl!("disable-package-title", name = name))
+                    .body(fl!("disable-package-body"))
+                    .primary_action(
+                        widget::button::destructive(fl!("remove-from-system"))
+                            .on_press(Message::ConfirmDialog),
+                    )
+                    .secondary_action(
+                        widget::button::standard(fl!("keep-installed"))
+                            .on_press(Message::DisablePackage(*id, false)),
+                    )
+                    .tertiary_action(
+                        widget::button::text(fl!("cancel")).on_press(Message::CancelDialog),
+                    )
+            }
+        };
+
+        Some(dialog.into())
+    }
+
     /// Describes the interface based on the current state of the application model.
     ///
     /// Application events will be processed through the view. Any messages emitted by
     /// events received by widgets will be passed to the update method.
     fn view(&self) -> Element<Self::Message> {
-        match self.nav.active_data::<Page>().cloned().unwrap_or_default() {
-            Page::Shells => self.shells.view().map(|m| Message::Page(Page::Shells, m)),
-            Page::Languages => self
-                .languages
-                .view()
-                .map(|m| Message::Page(Page::Languages, m)),
-            Page::Editors => self.editors.view().map(|m| Message::Page(Page::Editors, m)),
-            Page::Libraries => self
-                .libraries
-                .view()
-                .map(|m| Message::Page(Page::Libraries, m)),
-            Page::Tools => self.tools.view().map(|m| Message::Page(Page::Tools, m)),
-        }
+        let page = self.page_view();
+
+        let Some((summary, _)) = self.undo.last() else {
+            return page;
+        };
+
+        let spacing = theme::active().cosmic().spacing;
+        let undo = widget::row()
+            .push(widget::text(summary.clone()))
+            .push(widget::horizontal_space(Length::Fill))
+            .push(widget::button::standard(fl!("undo")).on_press(Message::Undo))
+            .align_items(Alignment::Center)
+            .spacing(spacing.space_xxs)
+            .padding([spacing.space_xxs, 10]);
+
+        widget::column().push(undo).push(page).into()
     }
 
     /// Register subscriptions for this application.
@@ -215,8 +409,9 @@ impl Application for AppModel {
     /// beginning of the application, and persist through its lifetime.
     fn subscription(&self) -> Subscription<Self::Message> {
         struct MySubscription;
+        struct EngineSubscription;
 
-        Subscription::batch(vec![
+        let mut subscriptions = vec![
             // Create a subscription which emits updates through a channel.
             cosmic::iced::subscription::channel(
                 std::any::TypeId::of::<MySubscription>(),
@@ -237,7 +432,28 @@ impl Application for AppModel {
 
                     Message::UpdateConfig(update.config)
                 }),
-        ])
+        ];
+
+        // Run the engine for the active run, forwarding its progress as messages.
+        if let Some(run) = self.run.as_ref().filter(|run| !run.is_finished()) {
+            let plan = run.plan.clone();
+            subscriptions.push(cosmic::iced::subscription::channel(
+                (std::any::TypeId::of::<EngineSubscription>(), run.id),
+                100,
+                move |mut channel| async move {
+                    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+                    tokio::spawn(Engine::new(plan).run(sender));
+
+                    while let Some(event) = receiver.recv().await {
+                        _ = channel.send(Message::Engine(event)).await;
+                    }
+
+                    futures_util::future::pending().await
+                },
+            ));
+        }
+
+        Subscription::batch(subscriptions)
     }
 
     /// Handles messages emitted by the application and its widgets.
@@ -256,10 +472,6 @@ impl Application for AppModel {
                 // For example purposes only.
             }
             Message::ToggleContextPage(context_page) => {
-                if context_page == ContextPage::NewPackage {
-                    self.package = None;
-                }
-
                 if self.context_page == context_page {
                     // Close the context drawer if the toggled context page is the same.
                     self.core.window.show_context = !self.core.window.show_context;
@@ -272,28 +484,257 @@ impl Application for AppModel {
                 self.set_context_title(context_page.title());
             }
             Message::UpdateConfig(config) => {
+                self.profile_names = Self::profile_names(&config);
                 self.config = config;
+                self.update_statuses();
+                commands.push(self.update_drift());
             }
-            Message::EditPackage(id) => {
-                self.package = Some(id);
-                return Command::batch(vec![
-                    self.update(Message::ToggleContextPage(ContextPage::EditPackage))
-                ]);
+            Message::Open => {
+                return Command::perform(
+                    async {
+                        rfd::AsyncFileDialog::new()
+                            .add_filter(fl!("blueprint-files"), &blueprint::Format::EXTENSIONS)
+                            .pick_file()
+                            .await
+                            .map(|file| file.path().to_path_buf())
+                    },
+                    |path| cosmic::app::Message::App(Message::Load(path)),
+                );
             }
-            Message::NewPackage => {
-                let package = Package::new("Fish", Source::Apt("fish".into()), Page::Shells);
-                let mut packages = self.config.packages.clone();
-                packages.push(package);
+            Message::Load(Some(path)) => match Blueprint::load(&path) {
+                Ok(blueprint) => {
+                    // Opening a blueprint again replaces the profile it was opened into.
+                    let id = blueprint.id;
+                    let mut profiles = self.config.profiles.clone();
+                    match profiles.iter_mut().find(|profile| profile.id == id) {
+                        Some(profile) => *profile = blueprint,
+                        None => profiles.push(blueprint),
+                    }
+                    commands.push(self.set_profiles(profiles, id));
+                    self.file = Some(path);
+                }
+                Err(err) => log::error!("failed to open blueprint: {}", err),
+            },
+            Message::Load(None) | Message::SaveTo(None) => {}
+            Message::Save => match self.file.clone() {
+                Some(path) => commands.push(self.update(Message::SaveTo(Some(path)))),
+                None => commands.push(self.update(Message::SaveAs)),
+            },
+            Message::SaveAs => {
+                return Command::perform(
+                    async {
+                        rfd::AsyncFileDialog::new()
+                            .add_filter(fl!("blueprint-files"), &blueprint::Format::EXTENSIONS)
+                            .set_file_name("blueprint.ron")
+                            .save_file()
+                            .await
+                            .map(|file| file.path().to_path_buf())
+                    },
+                    |path| cosmic::app::Message::App(Message::SaveTo(path)),
+                );
+            }
+            Message::SaveTo(Some(path)) => {
+                let Some(mut blueprint) = self.config.active().cloned() else {
+                    return Command::none();
+                };
+                blueprint.version = blueprint::VERSION;
+
+                match blueprint.save(&path) {
+                    Ok(()) => self.file = Some(path),
+                    Err(err) => log::error!("failed to save blueprint: {}", err),
+                }
+            }
+            Message::SelectProfile(index) => {
+                if let Some(profile) = self.config.profiles.get(index) {
+                    let profiles = self.config.profiles.clone();
+                    let id = profile.id;
+                    commands.push(self.set_profiles(profiles, id));
+                }
+            }
+            Message::NewProfile => {
+                let profile = Blueprint::new(&fl!("untitled-profile"), vec![]);
+                let id = profile.id;
+                let mut profiles = self.config.profiles.clone();
+                profiles.push(profile);
+                commands.push(self.set_profiles(profiles, id));
+
+                commands.push(self.open_context_page(ContextPage::Profile));
+            }
+            Message::DuplicateProfile => {
+                if let Some(active) = self.config.active() {
+                    let mut profile = active.clone();
+                    profile.id = Uuid::new_v4();
+                    profile.name = fl!("profile-copy", name = active.name.clone());
+                    let id = profile.id;
+                    let mut profiles = self.config.profiles.clone();
+                    profiles.push(profile);
+                    commands.push(self.set_profiles(profiles, id));
+                }
+            }
+            Message::DeleteProfile => {
+                if let Some(index) = self.config.active_index().filter(|_| {
+                    // Always keep at least one profile around.
+                    self.config.profiles.len() > 1
+                }) {
+                    let mut profiles = self.config.profiles.clone();
+                    profiles.remove(index);
+                    let id = profiles[index.saturating_sub(1)].id;
+                    commands.push(self.set_profiles(profiles, id));
+                    self.core.window.show_context = false;
+                }
+            }
+            Message::ProfileName(name) => {
                 if let Some(config) = &mut self.config_handler {
-                    if let Err(err) = self.config.set_packages(config, packages.clone()) {
-                        log::error!("failed to set packages: {}", err);
+                    if let Err(err) = self
+                        .config
+                        .update_active(config, |profile| profile.name = name)
+                    {
+                        log::error!("failed to rename profile: {}", err);
                     }
                 }
+                commands.push(self.update(Message::UpdateConfig(self.config.clone())));
+            }
+            Message::ProfileDescription(description) => {
+                if let Some(config) = &mut self.config_handler {
+                    if let Err(err) = self
+                        .config
+                        .update_active(config, |profile| profile.description = description)
+                    {
+                        log::error!("failed to set profile description: {}", err);
+                    }
+                }
+            }
+            Message::AddVariable => commands.push(self.update_variables(|variables| {
+                variables.push(Variable::default());
+            })),
+            Message::RemoveVariable(index) => commands.push(self.update_variables(|variables| {
+                if index < variables.len() {
+                    variables.remove(index);
+                }
+            })),
+            Message::VariableName(index, name) => {
+                commands.push(self.update_variables(|variables| {
+                    if let Some(variable) = variables.get_mut(index) {
+                        variable.name = name;
+                    }
+                }))
+            }
+            Message::VariableDescription(index, description) => {
+                commands.push(self.update_variables(|variables| {
+                    if let Some(variable) = variables.get_mut(index) {
+                        variable.description = description;
+                    }
+                }))
+            }
+            Message::VariableDefault(index, default) => {
+                commands.push(self.update_variables(|variables| {
+                    if let Some(variable) = variables.get_mut(index) {
+                        variable.default = default;
+                    }
+                }))
+            }
+            Message::VariableValue(name, value) => {
+                let mut values = self.config.variables.clone();
+                if value.is_empty() {
+                    values.remove(&name);
+                } else {
+                    values.insert(name, value);
+                }
 
-                return Command::batch(vec![
-                    self.update(Message::UpdateConfig(self.config.clone())),
-                    self.update(Message::Page(page, page::Message::ReloadPackages)),
-                ]);
+                if let Some(config) = &mut self.config_handler {
+                    if let Err(err) = self.config.set_variables(config, values) {
+                        log::error!("failed to save variable: {}", err);
+                    }
+                }
+                commands.push(self.update_drift());
+            }
+            Message::EditPackage(id) => {
+                if let Some(package) = self.config.active_packages().iter().find(|p| p.id == id) {
+                    self.editor = Some(PackageEditor::edit(package.clone()));
+                    commands.push(self.open_context_page(ContextPage::EditPackage));
+                }
+            }
+            Message::NewPackage => {
+                let kind = self.host.preferred_kind().unwrap_or(Kind::Apt);
+                self.editor = Some(PackageEditor::new(page, kind));
+                commands.push(self.open_context_page(ContextPage::NewPackage));
+            }
+            Message::Editor(message) => {
+                let editor_commands = match &mut self.editor {
+                    Some(editor) => editor.update(message),
+                    None => vec![],
+                };
+                for command in editor_commands {
+                    match command {
+                        editor::Command::Save(package) => {
+                            if let Err(err) = engine::deploy::push_linked(&package) {
+                                log::error!("failed to update linked config files: {}", err);
+                            }
+
+                            let mut packages = self.config.active_packages().to_vec();
+                            match packages.iter_mut().find(|p| p.id == package.id) {
+                                Some(existing) => *existing = package,
+                                None => packages.push(package),
+                            }
+                            self.editor = None;
+                            self.core.window.show_context = false;
+                            commands.push(self.set_packages(packages));
+                        }
+                        editor::Command::ImportFiles => commands.push(Command::perform(
+                            async {
+                                let mut dialog =
+                                    rfd::AsyncFileDialog::new().set_title(fl!("import-files"));
+                                if let Some(home) = dirs::home_dir() {
+                                    dialog = dialog.set_directory(home);
+                                }
+
+                                let paths: Vec<PathBuf> = dialog
+                                    .pick_files()
+                                    .await
+                                    .unwrap_or_default()
+                                    .iter()
+                                    .map(|file| file.path().to_path_buf())
+                                    .collect();
+
+                                tokio::task::spawn_blocking(move || {
+                                    paths
+                                        .iter()
+                                        .filter_map(|path| match ConfigFile::import(path) {
+                                            Ok(file) => Some(file),
+                                            Err(err) => {
+                                                log::error!(
+                                                    "failed to import {}: {}",
+                                                    path.display(),
+                                                    err
+                                                );
+                                                None
+                                            }
+                                        })
+                                        .collect()
+                                })
+                                .await
+                                .unwrap_or_default()
+                            },
+                            |files| {
+                                cosmic::app::Message::App(Message::Editor(
+                                    editor::Message::Imported(files),
+                                ))
+                            },
+                        )),
+                        editor::Command::Duplicate(id) => {
+                            self.editor = None;
+                            self.core.window.show_context = false;
+                            commands.push(self.update(Message::DuplicatePackage(id)));
+                        }
+                        editor::Command::Delete(id) => {
+                            commands.push(self.update(Message::DeletePackage(id)))
+                        }
+                        editor::Command::Cancel => {
+                            self.editor = None;
+                            self.core.window.show_context = false;
+                        }
+                    }
+                }
             }
             Message::Page(page, message) => {
                 let page_commands = match page {
@@ -305,40 +746,345 @@ impl Application for AppModel {
                 };
                 for command in page_commands {
                     match command {
+                        page::Command::ShowDrift(id) => {
+                            commands.push(self.update(Message::ShowDrift(id)))
+                        }
                         page::Command::EditPackage(id) => {
                             commands.push(self.update(Message::EditPackage(id)))
                         }
+                        page::Command::TogglePackage(id, enabled) => {
+                            commands.push(self.update(Message::TogglePackage(id, enabled)))
+                        }
+                        page::Command::DuplicatePackage(id) => {
+                            commands.push(self.update(Message::DuplicatePackage(id)))
+                        }
+                        page::Command::MovePackage(id, page) => {
+                            commands.push(self.update(Message::MovePackage(id, page)))
+                        }
+                        page::Command::DeletePackage(id) => {
+                            commands.push(self.update(Message::DeletePackage(id)))
+                        }
                     }
                 }
             }
-            Message::PackageTitle(index, title) => {
-                let mut packages = self.config.packages.clone();
-                packages[index].name = title;
-                if let Some(config) = &mut self.config_handler {
-                    if let Err(err) = self.config.set_packages(config, packages.clone()) {
-                        log::error!("failed to set packages: {}", err);
+            Message::TogglePackage(id, toggled) => {
+                let installed = self.config.active_packages().iter().any(|package| {
+                    package.id == id
+                        && self.installed.as_ref().is_some_and(|installed| {
+                            installed.status(self.host.source_for(package)) != Status::Missing
+                        })
+                });
+
+                if !toggled && installed {
+                    // Ask whether the package should also leave this machine.
+                    self.dialog = Some(Dialog::DisablePackage(id));
+                    return Command::batch(commands);
+                }
+
+                let mut packages = self.config.active_packages().to_vec();
+                if let Some(package) = packages.iter_mut().find(|p| p.id == id) {
+                    package.enabled = toggled;
+                    commands.push(self.set_packages(packages));
+                }
+            }
+            Message::DisablePackage(id, remove) => {
+                self.dialog = None;
+                let mut packages = self.config.active_packages().to_vec();
+                let Some(package) = packages.iter_mut().find(|p| p.id == id) else {
+                    return Command::batch(commands);
+                };
+                package.enabled = false;
+
+                if remove {
+                    let installed = self.installed.clone().unwrap_or_default();
+                    let mut plan = Plan::new(
+                        &[package.clone()],
+                        &installed,
+                        &self.host,
+                        &Default::default(),
+                    );
+                    for step in &mut plan.packages {
+                        step.approved = step.action == plan::Action::Remove;
                     }
-                    commands.push(self.update(Message::Page(page, page::Message::ReloadPackages)));
+                    self.plan = Some(plan);
+                    commands.push(self.update(Message::Apply));
                 }
+
+                commands.push(self.set_packages(packages));
             }
-            Message::PackageDescription(index, description) => {
-                let mut packages = self.config.packages.clone();
-                packages[index].description = description;
-                if let Some(config) = &mut self.config_handler {
-                    if let Err(err) = self.config.set_packages(config, packages.clone()) {
-                        log::error!("failed to set packages: {}", err);
+            Message::DuplicatePackage(id) => {
+                let packages = self.config.active_packages();
+                if let Some(index) = packages.iter().position(|p| p.id == id) {
+                    let name = packages[index].name.clone();
+                    let mut copy = packages[index].clone();
+                    copy.id = Uuid::new_v4();
+                    copy.name = fl!("package-copy", name = name.clone());
+
+                    commands.push(
+                        self.change_packages(fl!("duplicated-package", name = name), |packages| {
+                            packages.insert(index + 1, copy)
+                        }),
+                    );
+                }
+            }
+            Message::MovePackage(id, page) => {
+                if let Some(package) = self.config.active_packages().iter().find(|p| p.id == id) {
+                    let summary = fl!(
+                        "moved-package",
+                        name = package.name.clone(),
+                        page = page.to_string()
+                    );
+                    commands.push(self.change_packages(summary, |packages| {
+                        if let Some(package) = packages.iter_mut().find(|p| p.id == id) {
+                            package.page = page;
+                        }
+                    }));
+                }
+            }
+            Message::DeletePackage(id) => self.dialog = Some(Dialog::DeletePackage(id)),
+            Message::ShowDrift(id) => {
+                self.drift_package = Some(id);
+                commands.push(self.open_context_page(ContextPage::Drift));
+            }
+            Message::DriftDetected(drift) => {
+                self.drift = drift;
+                let drifted: HashSet<Uuid> = self.drift.keys().copied().collect();
+                for view in self.pages_mut() {
+                    view.update(page::Message::Drifted(drifted.clone()));
+                }
+            }
+            Message::OverwriteDrift(id, index) => {
+                let file = self
+                    .config
+                    .active_packages()
+                    .iter()
+                    .find(|package| package.id == id)
+                    .and_then(|package| package.config.get(index));
+                let drift = self
+                    .drift
+                    .get(&id)
+                    .and_then(|drift| drift.iter().find(|drift| drift.index == index));
+
+                // Written by the engine like any applied file, so it is journaled and
+                // can be reverted.
+                if self.run.as_ref().is_some_and(|run| !run.is_finished()) {
+                    log::warn!("a blueprint is already being applied");
+                } else if let (Some(file), Some(drift)) = (file, drift) {
+                    self.run = Some(Run::new(Plan::overwrite(id, file, drift)));
+                    commands.push(self.open_context_page(ContextPage::Apply));
+                }
+            }
+            Message::PullDrift(id, index) => {
+                let current = self
+                    .drift
+                    .get(&id)
+                    .and_then(|drift| drift.iter().find(|drift| drift.index == index))
+                    .map(|drift| drift.current.clone());
+
+                if let Some(current) = current {
+                    let mut packages = self.config.active_packages().to_vec();
+                    if let Some(file) = packages
+                        .iter_mut()
+                        .find(|package| package.id == id)
+                        .and_then(|package| package.config.get_mut(index))
+                    {
+                        file.content = current;
+                        commands.push(self.set_packages(packages));
                     }
-                    commands.push(self.update(Message::Page(page, page::Message::ReloadPackages)));
                 }
             }
-            Message::TogglePackage(index, toggled) => {
-                let mut packages = self.config.packages.clone();
-                packages[index].enabled = toggled;
-                if let Some(config) = &mut self.config_handler {
-                    if let Err(err) = self.config.set_packages(config, packages.clone()) {
-                        log::error!("failed to set packages: {}", err);
+            Message::ConfirmDialog => match self.dialog.take() {
+                Some(Dialog::DisablePackage(id)) => {
+                    commands.push(self.update(Message::DisablePackage(id, true)))
+                }
+                Some(Dialog::DeletePackage(id)) => {
+                    if self.editor.as_ref().is_some_and(|editor| editor.id() == id) {
+                        self.editor = None;
+                        self.core.window.show_context = false;
+                    }
+
+                    if let Some(package) = self.config.active_packages().iter().find(|p| p.id == id)
+                    {
+                        let summary = fl!("deleted-package", name = package.name.clone());
+                        commands.push(
+                            self.change_packages(summary, |packages| {
+                                packages.retain(|p| p.id != id)
+                            }),
+                        );
+                    }
+                }
+                None => {}
+            },
+            Message::CancelDialog => self.dialog = None,
+            Message::Undo => {
+                if let Some((_, packages)) = self.undo.pop() {
+                    commands.push(self.save_packages(packages));
+                }
+            }
+            Message::Plan => {
+                let variables = self.config.variables(&self.host);
+                if !template::missing(self.config.active_packages(), &variables).is_empty() {
+                    // Ask for the missing values first, planning again from there.
+                    commands.push(self.open_context_page(ContextPage::Variables));
+                    return Command::batch(commands);
+                }
+
+                let installed = self.installed.clone().unwrap_or_default();
+                self.plan = Some(Plan::new(
+                    self.config.active_packages(),
+                    &installed,
+                    &self.host,
+                    &variables,
+                ));
+
+                commands.push(self.open_context_page(ContextPage::Plan));
+            }
+            Message::ApproveStep(index, approved) => {
+                if let Some(step) = self
+                    .plan
+                    .as_mut()
+                    .and_then(|plan| plan.packages.get_mut(index))
+                {
+                    step.approved = approved;
+                }
+            }
+            Message::ApproveFile(index, approved) => {
+                if let Some(file) = self
+                    .plan
+                    .as_mut()
+                    .and_then(|plan| plan.files.get_mut(index))
+                {
+                    file.approved = approved;
+                }
+            }
+            Message::Apply => {
+                if self.run.as_ref().is_some_and(|run| !run.is_finished()) {
+                    log::warn!("a blueprint is already being applied");
+                } else if let Some(plan) = self.plan.take() {
+                    self.run = Some(Run::new(plan));
+                }
+
+                commands.push(self.open_context_page(ContextPage::Apply));
+            }
+            Message::Revert => {
+                let installed = self.installed.clone().unwrap_or_default();
+                self.plan = Some(Plan::revert(&self.config.applied, &installed));
+                commands.push(self.open_context_page(ContextPage::Plan));
+            }
+            Message::History => {
+                commands.push(self.open_context_page(ContextPage::History));
+                commands.push(Command::perform(
+                    async {
+                        tokio::task::spawn_blocking(|| {
+                            Journal::open()
+                                .map(|journal| journal.load())
+                                .transpose()
+                                .unwrap_or_else(|err| {
+                                    log::error!("failed to read the journal: {}", err);
+                                    None
+                                })
+                                .unwrap_or_default()
+                        })
+                        .await
+                        .unwrap_or_default()
+                    },
+                    |history| cosmic::app::Message::App(Message::HistoryLoaded(history)),
+                ));
+            }
+            Message::HistoryLoaded(history) => self.history = history,
+            Message::Rollback(id) => {
+                if let Some(transaction) = self.history.iter().find(|run| run.id == id) {
+                    let installed = self.installed.clone().unwrap_or_default();
+                    self.plan = Some(Plan::revert(&transaction.applied(), &installed));
+                    commands.push(self.open_context_page(ContextPage::Plan));
+                }
+            }
+            Message::Engine(event) => {
+                let finished = matches!(event, engine::Event::Finished(_));
+                if let Some(run) = &mut self.run {
+                    run.handle(event);
+                }
+                if finished {
+                    // Remembers what the run changed, so it can be reverted.
+                    if let (Some(config), Some(run)) = (&self.config_handler, &self.run) {
+                        if let Some(report) = &run.report {
+                            if let Err(err) = self.config.record(config, &run.packages, report) {
+                                log::error!("failed to record the changes made: {}", err);
+                            }
+                        }
+                    }
+
+                    commands.push(self.update(Message::Detect));
+                }
+            }
+            Message::Scan => {
+                commands.push(self.open_context_page(ContextPage::Scan));
+                if self.scanning {
+                    return Command::batch(commands);
+                }
+
+                self.scanning = true;
+                self.scan.clear();
+                let host = self.host.clone();
+                commands.push(Command::perform(
+                    async move {
+                        tokio::task::spawn_blocking(move || engine::scan::scan(&host))
+                            .await
+                            .unwrap_or_default()
+                    },
+                    |packages| cosmic::app::Message::App(Message::Scanned(packages)),
+                ));
+            }
+            Message::Scanned(packages) => {
+                self.scanning = false;
+                self.scan = packages
+                    .into_iter()
+                    .map(|package| (package, true))
+                    .collect();
+            }
+            Message::SelectScanned(index, selected) => {
+                if let Some((_, keep)) = self.scan.get_mut(index) {
+                    *keep = selected;
+                }
+            }
+            Message::CreateFromScan => {
+                let packages = std::mem::take(&mut self.scan)
+                    .into_iter()
+                    .filter_map(|(package, keep)| keep.then_some(package))
+                    .collect();
+                let name = match self.host.os.pretty_name.as_str() {
+                    "" => fl!("scanned-profile"),
+                    name => name.to_string(),
+                };
+
+                let profile = Blueprint::new(&name, packages);
+                let id = profile.id;
+                let mut profiles = self.config.profiles.clone();
+                profiles.push(profile);
+                commands.push(self.set_profiles(profiles, id));
+                self.core.window.show_context = false;
+            }
+            Message::Detect => {
+                return Command::perform(
+                    async {
+                        tokio::task::spawn_blocking(|| Installed::load(std::path::Path::new("/")))
+                            .await
+                            .unwrap_or_default()
+                    },
+                    |installed| cosmic::app::Message::App(Message::Detected(installed)),
+                );
+            }
+            Message::Detected(installed) => {
+                self.installed = Some(installed);
+                self.update_statuses();
+                commands.push(self.update_drift());
+
+                if let Some(config) = &self.config_handler {
+                    match self.config.pull_linked(config) {
+                        Ok(true) => commands.push(self.reload_pages()),
+                        Ok(false) => {}
+                        Err(err) => log::error!("failed to save linked config files: {}", err),
                     }
-                    commands.push(self.update(Message::Page(page, page::Message::ReloadPackages)));
                 }
             }
         }
@@ -382,55 +1128,701 @@ impl AppModel {
             .into()
     }
 
-    pub fn package_view(&self) -> Element<Message> {
+    /// Editor for the name and description of the active profile.
+    pub fn profile_view(&self) -> Element<Message> {
         let cosmic_theme::Spacing { space_xxs, .. } = theme::active().cosmic().spacing;
 
-        let Some(id) = self.package else {
-            let create = widget::button(widget::text(fl!("create")))
-                .on_press(Message::NewPackage)
-                .padding(0);
-
-            return widget::column()
-                .push(create)
-                .align_items(Alignment::Center)
-                .spacing(space_xxs)
-                .into();
+        let Some(profile) = self.config.active() else {
+            return widget::text(fl!("no-profile")).into();
         };
 
-        let Some(index) = self.config.packages.iter().position(|p| p.id == id) else {
-            return widget::text("No package selected").into();
-        };
-
-        let package = self.config.packages[index].clone();
-
-        let title =
-            widget::settings::item_row(vec![widget::text_input(fl!("name"), package.name.clone())
-                .label("Name")
-                .on_input(move |text| Message::PackageTitle(index, text))
+        let name =
+            widget::settings::item_row(vec![widget::text_input(fl!("name"), profile.name.clone())
+                .label(fl!("name"))
+                .on_input(Message::ProfileName)
                 .into()]);
 
         let description = widget::settings::item_row(vec![widget::text_input(
             fl!("description"),
-            package.description.clone(),
+            profile.description.clone(),
         )
-        .label("Description")
-        .on_input(move |text| Message::PackageDescription(index, text))
+        .label(fl!("description"))
+        .on_input(Message::ProfileDescription)
         .into()]);
 
-        let enabled = widget::settings::item(
-            fl!("enabled"),
-            widget::checkbox(fl!("enabled"), package.enabled, move |toggled| {
-                Message::TogglePackage(index, toggled)
-            }),
-        );
+        let mut variables = widget::settings::view_section(fl!("variables"))
+            .add(widget::text::caption(fl!("variables-desc")));
+        for (index, variable) in profile.variables.iter().enumerate() {
+            variables = variables.add(
+                widget::row()
+                    .push(
+                        widget::column()
+                            .push(
+                                widget::text_input(fl!("variable-name"), variable.name.clone())
+                                    .label(fl!("name"))
+                                    .on_input(move |name| Message::VariableName(index, name)),
+                            )
+                            .push(
+                                widget::text_input(
+                                    fl!("description"),
+                                    variable.description.clone(),
+                                )
+                                .label(fl!("description"))
+                                .on_input(move |description| {
+                                    Message::VariableDescription(index, description)
+                                }),
+                            )
+                            .push(
+                                widget::text_input(
+                                    fl!("variable-default-placeholder"),
+                                    variable.default.clone(),
+                                )
+                                .label(fl!("variable-default"))
+                                .on_input(move |default| Message::VariableDefault(index, default)),
+                            )
+                            .spacing(space_xxs),
+                    )
+                    .push(
+                        widget::button::icon(icon::from_name("user-trash-symbolic"))
+                            .on_press(Message::RemoveVariable(index)),
+                    )
+                    .align_items(Alignment::Center)
+                    .spacing(space_xxs),
+            );
+        }
+
+        let delete = widget::button::destructive(fl!("delete-profile"))
+            .on_press_maybe((self.config.profiles.len() > 1).then_some(Message::DeleteProfile));
+
+        let actions = widget::row()
+            .push(widget::button::standard(fl!("add-variable")).on_press(Message::AddVariable))
+            .push(widget::horizontal_space(Length::Fill))
+            .push(delete)
+            .spacing(space_xxs);
+
+        widget::column()
+            .push(
+                widget::settings::view_section(fl!("profile"))
+                    .add(name)
+                    .add(description),
+            )
+            .push(variables)
+            .push(actions)
+            .spacing(space_xxs)
+            .into()
+    }
+
+    /// The values of the variables used by templated config files on this machine.
+    pub fn variables_view(&self) -> Element<Message> {
+        let cosmic_theme::Spacing { space_xxs, .. } = theme::active().cosmic().spacing;
+
+        let values = self.config.variables(&self.host);
+        let missing = template::missing(self.config.active_packages(), &values);
+        let declared = self
+            .config
+            .active()
+            .map(|profile| profile.variables.as_slice())
+            .unwrap_or_default();
+
+        let mut column = widget::column().spacing(space_xxs);
+
+        if !missing.is_empty() {
+            column = column.push(widget::text(fl!(
+                "missing-variables",
+                names = missing.join(", ")
+            )));
+        }
 
-        widget::settings::view_section(fl!("package"))
-            .add(title)
-            .add(description)
-            .add(enabled)
+        let mut section = widget::settings::view_section(fl!("variables"));
+        if declared.is_empty() {
+            section = section.add(widget::text(fl!("no-variables")));
+        }
+        for variable in declared {
+            let name = variable.name.clone();
+            let value = self
+                .config
+                .variables
+                .get(&variable.name)
+                .cloned()
+                .unwrap_or_default();
+
+            let input = widget::text_input(variable.default.clone(), value)
+                .label(variable.name.clone())
+                .on_input(move |value| Message::VariableValue(name.clone(), value));
+            let description = (!variable.description.is_empty())
+                .then(|| widget::text::caption(variable.description.clone()));
+
+            section = section.add(
+                widget::column()
+                    .push(input)
+                    .push_maybe(description)
+                    .spacing(space_xxs),
+            );
+        }
+
+        let mut builtins = widget::settings::view_section(fl!("builtin-variables"));
+        for name in template::BUILTINS {
+            let value = values.get(name).cloned().unwrap_or_default();
+            builtins = builtins.add(widget::settings::item(name, widget::text(value)));
+        }
+
+        column
+            .push(section)
+            .push(builtins)
+            .push(
+                widget::button::suggested(fl!("review-changes"))
+                    .on_press_maybe(missing.is_empty().then_some(Message::Plan)),
+            )
             .into()
     }
 
+    /// The config files of a package that were changed on disk, next to the
+    /// blueprint's contents.
+    pub fn drift_view(&self) -> Element<Message> {
+        let cosmic_theme::Spacing { space_xxs, .. } = theme::active().cosmic().spacing;
+
+        let Some(id) = self.drift_package else {
+            return widget::text(fl!("no-drift")).into();
+        };
+        let (Some(package), Some(drift)) = (
+            self.config.active_packages().iter().find(|p| p.id == id),
+            self.drift.get(&id),
+        ) else {
+            return widget::text(fl!("no-drift")).into();
+        };
+
+        let mut column = widget::column().spacing(space_xxs);
+
+        for drift in drift {
+            let Some(file) = package.config.get(drift.index) else {
+                continue;
+            };
+
+            let line = |line: Option<String>, marker: &str| {
+                let text = line
+                    .map(|line| format!("{marker} {line}"))
+                    .unwrap_or_default();
+                widget::text::body(text)
+                    .font(cosmic::font::mono())
+                    .width(Length::FillPortion(1))
+            };
+
+            let mut diff = widget::column().push(
+                widget::row()
+                    .push(widget::text::heading(fl!("blueprint")).width(Length::FillPortion(1)))
+                    .push(widget::text::heading(fl!("on-disk")).width(Length::FillPortion(1)))
+                    .spacing(space_xxs),
+            );
+            for row in engine::drift::side_by_side(&drift.expected, &drift.current) {
+                let (left, right) = if row.is_changed() {
+                    ("-", "+")
+                } else {
+                    (" ", " ")
+                };
+                diff = diff.push(
+                    widget::row()
+                        .push(line(row.left, left))
+                        .push(line(row.right, right))
+                        .spacing(space_xxs),
+                );
+            }
+
+            let actions = widget::row()
+                .push(widget::horizontal_space(Length::Fill))
+                .push(
+                    // Pulling would replace the placeholders of a template, or the
+                    // partial document of a merged file with the whole file.
+                    widget::button::standard(fl!("pull-drift")).on_press_maybe(
+                        (!file.template && file.method != DeployMethod::Merge)
+                            .then_some(Message::PullDrift(id, drift.index)),
+                    ),
+                )
+                .push(
+                    widget::button::destructive(fl!("overwrite-drift"))
+                        .on_press(Message::OverwriteDrift(id, drift.index)),
+                )
+                .spacing(space_xxs);
+
+            column = column.push(
+                widget::settings::view_section(drift.target.display().to_string())
+                    .add(diff)
+                    .add(actions),
+            );
+        }
+
+        column.into()
+    }
+
+    /// The changes applying the blueprint would make, for review.
+    pub fn plan_view(&self) -> Element<Message> {
+        let cosmic_theme::Spacing { space_xxs, .. } = theme::active().cosmic().spacing;
+
+        let Some(plan) = &self.plan else {
+            return widget::text(fl!("no-packages")).into();
+        };
+
+        let mut packages = widget::settings::view_section(fl!("packages"));
+
+        if plan.packages.is_empty() {
+            packages = packages.add(widget::text(fl!("no-packages")));
+        }
+
+        for (index, step) in plan.packages.iter().enumerate() {
+            let action = match step.action {
+                plan::Action::Install => fl!("install"),
+                plan::Action::Skip => fl!("skip"),
+                plan::Action::Remove => fl!("remove"),
+                plan::Action::Unavailable => fl!("unavailable"),
+                plan::Action::Invalid => fl!("invalid-package"),
+                plan::Action::Bootstrap => fl!("bootstrap-rustup"),
+            };
+
+            let control: Element<Message> = if matches!(
+                step.action,
+                plan::Action::Skip | plan::Action::Unavailable | plan::Action::Invalid
+            ) {
+                widget::text(action).into()
+            } else {
+                widget::checkbox(action, step.approved, move |approved| {
+                    Message::ApproveStep(index, approved)
+                })
+                .into()
+            };
+
+            packages = packages.add(widget::settings::item(step.package.name.clone(), control));
+        }
+
+        let mut column = widget::column().push(packages).spacing(space_xxs);
+
+        if !plan.files.is_empty() {
+            let mut files = widget::settings::view_section(fl!("files"));
+
+            for (index, file) in plan.files.iter().enumerate() {
+                let action = match file.action {
+                    plan::FileAction::Create => fl!("create-file"),
+                    plan::FileAction::Overwrite => fl!("overwrite-file"),
+                    plan::FileAction::Unchanged => fl!("unchanged-file"),
+                    plan::FileAction::Invalid => fl!("invalid-file"),
+                    plan::FileAction::Restore if file.backup.is_some() => fl!("restore-file"),
+                    plan::FileAction::Restore => fl!("delete-file"),
+                };
+
+                let control: Element<Message> = if matches!(
+                    file.action,
+                    plan::FileAction::Unchanged | plan::FileAction::Invalid
+                ) {
+                    widget::text(action).into()
+                } else {
+                    widget::checkbox(action, file.approved, move |approved| {
+                        Message::ApproveFile(index, approved)
+                    })
+                    .into()
+                };
+
+                let mut item = widget::column()
+                    .push(widget::settings::item(
+                        file.target.display().to_string(),
+                        control,
+                    ))
+                    .spacing(space_xxs);
+
+                if file.action != plan::FileAction::Unchanged {
+                    item =
+                        item.push(widget::text::body(file.diff.clone()).font(cosmic::font::mono()));
+                }
+
+                files = files.add(item);
+            }
+
+            column = column.push(files);
+        }
+
+        let apply = widget::button::suggested(fl!("approve-and-apply"))
+            .on_press_maybe((!plan.is_empty()).then_some(Message::Apply));
+
+        column.push(apply).into()
+    }
+
+    /// The packages found on this machine, to pick the ones a new profile keeps.
+    pub fn scan_view(&self) -> Element<Message> {
+        let cosmic_theme::Spacing { space_xxs, .. } = theme::active().cosmic().spacing;
+
+        if self.scanning {
+            return widget::text(fl!("scanning")).into();
+        }
+
+        let mut column = widget::column()
+            .push(widget::text::caption(fl!("scan-desc")))
+            .spacing(space_xxs);
+
+        for page in Page::ALL {
+            let mut section = widget::settings::view_section(page.to_string());
+            let mut empty = true;
+
+            for (index, (package, keep)) in self.scan.iter().enumerate() {
+                if package.page != page {
+                    continue;
+                }
+
+                empty = false;
+                section = section.add(widget::settings::item_row(vec![
+                    widget::checkbox(package.name.clone(), *keep, move |keep| {
+                        Message::SelectScanned(index, keep)
+                    })
+                    .into(),
+                    widget::horizontal_space(Length::Fill).into(),
+                    widget::text::caption(package.source.to_string()).into(),
+                ]));
+            }
+
+            if !empty {
+                column = column.push(section);
+            }
+        }
+
+        if self.scan.is_empty() {
+            column = column.push(widget::text(fl!("nothing-found")));
+        }
+
+        let create = widget::button::suggested(fl!("create-profile")).on_press_maybe(
+            self.scan
+                .iter()
+                .any(|(_, keep)| *keep)
+                .then_some(Message::CreateFromScan),
+        );
+
+        column.push(create).into()
+    }
+
+    /// Past runs and what each did, with a way to roll each back.
+    pub fn history_view(&self) -> Element<Message> {
+        let cosmic_theme::Spacing { space_xxs, .. } = theme::active().cosmic().spacing;
+
+        if self.history.is_empty() {
+            return widget::text(fl!("no-history")).into();
+        }
+
+        let mut column = widget::column().spacing(space_xxs);
+
+        for transaction in &self.history {
+            let mut title = engine::deploy::date_time(transaction.started_at());
+            if !transaction.is_finished() {
+                title = fl!("interrupted-run", time = title);
+            }
+            let mut section = widget::settings::view_section(title);
+
+            for record in &transaction.records {
+                let (label, status, detail) = match record {
+                    Record::Started | Record::Finished => continue,
+                    Record::Command {
+                        command, status, ..
+                    } => {
+                        let status = match status {
+                            Some(code) => fl!("exit-status", code = *code),
+                            None => fl!("no-exit-status"),
+                        };
+                        (format!("$ {command}"), status, None)
+                    }
+                    Record::Installed { name, source, .. } => {
+                        (name.clone(), fl!("installed"), Some(source.to_string()))
+                    }
+                    Record::Removed { name, source, .. } => {
+                        (name.clone(), fl!("removed"), Some(source.to_string()))
+                    }
+                    Record::Failed { error, .. } => (error.clone(), fl!("failed"), None),
+                    Record::Written { target, backup, .. } => (
+                        target.display().to_string(),
+                        fl!("deployed-file"),
+                        backup
+                            .as_ref()
+                            .map(|backup| fl!("backup-file", path = backup.display().to_string())),
+                    ),
+                    Record::Restored { target } => {
+                        (target.display().to_string(), fl!("restored-file"), None)
+                    }
+                };
+
+                section = section.add(widget::settings::item_row(vec![
+                    widget::column()
+                        .push(widget::text(label))
+                        .push_maybe(detail.map(widget::text::caption))
+                        .spacing(space_xxs)
+                        .into(),
+                    widget::horizontal_space(Length::Fill).into(),
+                    widget::text(status).into(),
+                ]));
+            }
+
+            section = section.add(widget::settings::item_row(vec![
+                widget::horizontal_space(Length::Fill).into(),
+                widget::button::standard(fl!("roll-back"))
+                    .on_press(Message::Rollback(transaction.id))
+                    .into(),
+            ]));
+            column = column.push(section);
+        }
+
+        column.into()
+    }
+
+    /// The progress of the blueprint being applied.
+    pub fn run_view(&self) -> Element<Message> {
+        let cosmic_theme::Spacing { space_xxs, .. } = theme::active().cosmic().spacing;
+
+        let Some(run) = &self.run else {
+            return widget::text(fl!("no-packages")).into();
+        };
+
+        let mut section = widget::settings::view_section(fl!("packages"));
+
+        if run.packages.is_empty() {
+            section = section.add(widget::text(fl!("no-packages")));
+        }
+
+        for package in &run.packages {
+            let (status, error) = match run.state(&package.id) {
+                run::State::Pending => (fl!("pending"), None),
+                run::State::Installing => (fl!("installing"), None),
+                run::State::Removing => (fl!("removing"), None),
+                run::State::Installed => (fl!("installed"), None),
+                run::State::Removed => (fl!("removed"), None),
+                run::State::Failed(error) => (fl!("failed"), Some(error.clone())),
+            };
+
+            let detail = error.or_else(|| {
+                run.output
+                    .get(&package.id)
+                    .and_then(|lines| lines.last())
+                    .cloned()
+            });
+
+            section = section.add(widget::settings::item_row(vec![
+                widget::column()
+                    .push(widget::text(package.name.clone()))
+                    .push_maybe(detail.map(widget::text::caption))
+                    .spacing(space_xxs)
+                    .into(),
+                widget::horizontal_space(Length::Fill).into(),
+                widget::text(status).into(),
+            ]));
+        }
+
+        let mut column = widget::column().push(section).spacing(space_xxs);
+
+        if !run.deployed.is_empty() || !run.restored.is_empty() || !run.deploy_errors.is_empty() {
+            let mut files = widget::settings::view_section(fl!("files"));
+            for deployment in &run.deployed {
+                let backup = deployment.backup.as_ref().map(|backup| {
+                    widget::text::caption(fl!("backup-file", path = backup.display().to_string()))
+                });
+                files = files.add(widget::settings::item_row(vec![
+                    widget::column()
+                        .push(widget::text(deployment.target.display().to_string()))
+                        .push_maybe(backup)
+                        .spacing(space_xxs)
+                        .into(),
+                    widget::horizontal_space(Length::Fill).into(),
+                    widget::text(fl!("deployed-file")).into(),
+                ]));
+            }
+            for target in &run.restored {
+                files = files.add(widget::settings::item(
+                    target.display().to_string(),
+                    widget::text(fl!("restored-file")),
+                ));
+            }
+            for (target, error) in &run.deploy_errors {
+                files = files.add(widget::settings::item_row(vec![
+                    widget::column()
+                        .push(widget::text(target.display().to_string()))
+                        .push(widget::text::caption(error.clone()))
+                        .spacing(space_xxs)
+                        .into(),
+                    widget::horizontal_space(Length::Fill).into(),
+                    widget::text(fl!("failed")).into(),
+                ]));
+            }
+            column = column.push(files);
+        }
+
+        if let Some(report) = &run.report {
+            column = column.push(widget::text(fl!(
+                "apply-finished",
+                installed = report.installed.len(),
+                removed = report.removed.len(),
+                deployed = report.deployed.len(),
+                restored = report.restored.len(),
+                failed = report.failed.len()
+            )));
+        }
+
+        column.into()
+    }
+
+    fn profile_names(config: &Config) -> Vec<String> {
+        config
+            .profiles
+            .iter()
+            .map(|profile| profile.name.clone())
+            .collect()
+    }
+
+    /// The view of the page selected in the nav bar.
+    fn page_view(&self) -> Element<Message> {
+        match self.nav.active_data::<Page>().cloned().unwrap_or_default() {
+            Page::Shells => self.shells.view().map(|m| Message::Page(Page::Shells, m)),
+            Page::Languages => self
+                .languages
+                .view()
+                .map(|m| Message::Page(Page::Languages, m)),
+            Page::Editors => self.editors.view().map(|m| Message::Page(Page::Editors, m)),
+            Page::Libraries => self
+                .libraries
+                .view()
+                .map(|m| Message::Page(Page::Libraries, m)),
+            Page::Tools => self.tools.view().map(|m| Message::Page(Page::Tools, m)),
+        }
+    }
+
+    /// Opens the context drawer on `context_page`, unless it is already showing.
+    fn open_context_page(&mut self, context_page: ContextPage) -> Command<Message> {
+        if self.context_page != context_page || !self.core.window.show_context {
+            self.update(Message::ToggleContextPage(context_page))
+        } else {
+            Command::none()
+        }
+    }
+
+    /// Changes the packages of the active profile with `change`, keeping the previous
+    /// packages around so the change can be undone.
+    fn change_packages(
+        &mut self,
+        summary: String,
+        change: impl FnOnce(&mut Vec<Package>),
+    ) -> Command<Message> {
+        let previous = self.config.active_packages().to_vec();
+        let mut packages = previous.clone();
+        change(&mut packages);
+
+        self.undo.push((summary, previous));
+        self.save_packages(packages)
+    }
+
+    /// Saves the packages of the active profile and reloads every page.
+    ///
+    /// Changes made before can no longer be undone.
+    fn set_packages(&mut self, packages: Vec<Package>) -> Command<Message> {
+        self.undo.clear();
+        self.save_packages(packages)
+    }
+
+    /// Saves the packages of the active profile and reloads every page, keeping the
+    /// changes that can be undone.
+    fn save_packages(&mut self, packages: Vec<Package>) -> Command<Message> {
+        if let Some(config) = &mut self.config_handler {
+            if let Err(err) = self.config.set_active_packages(config, packages) {
+                log::error!("failed to set packages: {}", err);
+            }
+        }
+
+        self.reload_pages()
+    }
+
+    /// Saves `profiles`, activates the one with `active` and reloads every page.
+    fn set_profiles(&mut self, profiles: Vec<Blueprint>, active: Uuid) -> Command<Message> {
+        self.undo.clear();
+        if self.config.active_profile != active {
+            self.file = None;
+        }
+
+        if let Some(config) = &mut self.config_handler {
+            if let Err(err) = self.config.set_profiles(config, profiles) {
+                log::error!("failed to set profiles: {}", err);
+            }
+            if let Err(err) = self.config.set_active_profile(config, active) {
+                log::error!("failed to set active profile: {}", err);
+            }
+        }
+
+        self.reload_pages()
+    }
+
+    /// Reloads the packages of every page from the configuration.
+    fn reload_pages(&mut self) -> Command<Message> {
+        for view in self.pages_mut() {
+            view.update(page::Message::ReloadPackages);
+        }
+
+        self.update(Message::UpdateConfig(self.config.clone()))
+    }
+
+    fn pages_mut(&mut self) -> [&mut PageView; 5] {
+        [
+            &mut self.shells,
+            &mut self.languages,
+            &mut self.editors,
+            &mut self.libraries,
+            &mut self.tools,
+        ]
+    }
+
+    /// Changes the variables declared by the active profile with `change` and saves them.
+    fn update_variables(&mut self, change: impl FnOnce(&mut Vec<Variable>)) -> Command<Message> {
+        if let Some(config) = &mut self.config_handler {
+            if let Err(err) = self
+                .config
+                .update_active(config, |profile| change(&mut profile.variables))
+            {
+                log::error!("failed to save variables: {}", err);
+            }
+        }
+        self.update_drift()
+    }
+
+    /// Looks for config files changed on disk in the background, reading every
+    /// deployed file of the active profile.
+    fn update_drift(&self) -> Command<Message> {
+        let variables = self.config.variables(&self.host);
+        let packages = self.config.active_packages().to_vec();
+        let applied = self.config.applied.clone();
+
+        Command::perform(
+            async move {
+                tokio::task::spawn_blocking(move || {
+                    packages
+                        .iter()
+                        .map(|package| {
+                            let drift = engine::drift::detect(package, &applied, &variables);
+                            (package.id, drift)
+                        })
+                        .filter(|(_, drift)| !drift.is_empty())
+                        .collect()
+                })
+                .await
+                .unwrap_or_default()
+            },
+            |drift| cosmic::app::Message::App(Message::DriftDetected(drift)),
+        )
+    }
+
+    /// Sends the installed state of every package to the pages.
+    fn update_statuses(&mut self) {
+        let Some(installed) = &self.installed else {
+            return;
+        };
+
+        let statuses: HashMap<Uuid, engine::detect::Status> = self
+            .config
+            .active_packages()
+            .iter()
+            .map(|package| (package.id, installed.status(self.host.source_for(package))))
+            .collect();
+
+        for view in self.pages_mut() {
+            view.update(page::Message::Statuses(statuses.clone()));
+        }
+    }
+
     /// Updates the header and window titles.
     pub fn update_title(&mut self) -> Command<Message> {
         let mut window_title = fl!("app-title");
@@ -455,6 +1847,16 @@ pub enum Page {
     Tools,
 }
 
+impl Page {
+    pub const ALL: [Page; 5] = [
+        Page::Shells,
+        Page::Languages,
+        Page::Editors,
+        Page::Libraries,
+        Page::Tools,
+    ];
+}
+
 impl ToString for Page {
     fn to_string(&self) -> String {
         match self {
@@ -467,6 +1869,14 @@ impl ToString for Page {
     }
 }
 
+/// A dialog asking the user to confirm an action.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Dialog {
+    DeletePackage(Uuid),
+    /// Asks whether disabling an installed package also removes it.
+    DisablePackage(Uuid),
+}
+
 /// The context page to display in the context drawer.
 #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
 pub enum ContextPage {
@@ -474,6 +1884,13 @@ pub enum ContextPage {
     About,
     NewPackage,
     EditPackage,
+    Profile,
+    Plan,
+    Apply,
+    Drift,
+    Variables,
+    Scan,
+    History,
 }
 
 impl ContextPage {
@@ -482,6 +1899,13 @@ impl ContextPage {
             Self::About => fl!("about"),
             Self::NewPackage => fl!("new-package"),
             Self::EditPackage => fl!("edit-package"),
+            Self::Profile => fl!("profile"),
+            Self::Plan => fl!("plan"),
+            Self::Apply => fl!("apply"),
+            Self::Drift => fl!("drift"),
+            Self::Variables => fl!("variables"),
+            Self::Scan => fl!("scan-system"),
+            Self::History => fl!("history"),
         }
     }
 }
@@ -489,6 +1913,18 @@ impl ContextPage {
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum MenuAction {
     NewPackage,
+    Scan,
+    Open,
+    Save,
+    SaveAs,
+    Undo,
+    NewProfile,
+    DuplicateProfile,
+    EditProfile,
+    Variables,
+    Apply,
+    Revert,
+    History,
     About,
 }
 
@@ -498,7 +1934,19 @@ impl menu::action::MenuAction for MenuAction {
     fn message(&self) -> Self::Message {
         match self {
             MenuAction::About => Message::ToggleContextPage(ContextPage::About),
-            MenuAction::NewPackage => Message::ToggleContextPage(ContextPage::NewPackage),
+            MenuAction::NewPackage => Message::NewPackage,
+            MenuAction::Scan => Message::Scan,
+            MenuAction::Open => Message::Open,
+            MenuAction::Save => Message::Save,
+            MenuAction::SaveAs => Message::SaveAs,
+            MenuAction::Undo => Message::Undo,
+            MenuAction::NewProfile => Message::NewProfile,
+            MenuAction::DuplicateProfile => Message::DuplicateProfile,
+            MenuAction::EditProfile => Message::ToggleContextPage(ContextPage::Profile),
+            MenuAction::Variables => Message::ToggleContextPage(ContextPage::Variables),
+            MenuAction::Apply => Message::Plan,
+            MenuAction::Revert => Message::Revert,
+            MenuAction::History => Message::History,
         }
     }
 }
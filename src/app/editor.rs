@@ -0,0 +1,672 @@
+// SPDX-License-Identifier: GPL-3.0
+
+use std::path::PathBuf;
+
+use cosmic::{
+    iced::{widget::text_editor, Alignment, Length},
+    prelude::CollectionWidget,
+    widget::{self, icon},
+    Element,
+};
+
+use uuid::Uuid;
+
+use crate::engine::deploy::{self, LinkStatus};
+use crate::fl;
+
+use super::{
+    models::package::{ConfigFile, DeployMethod, Invalid, Kind, Package, Source},
+    Page,
+};
+
+/// A form for creating or editing every field of a [`Package`].
+pub struct PackageEditor {
+    /// The package being edited, only saved once it is valid.
+    draft: Package,
+    /// Whether the package is not part of the blueprint yet.
+    new: bool,
+    /// Whether saving was attempted, after which errors are shown inline.
+    submitted: bool,
+    errors: Vec<Invalid>,
+    kinds: Vec<String>,
+    pages: Vec<String>,
+    /// Editable contents of each config file, in the same order as `draft.config`.
+    contents: Vec<text_editor::Content>,
+    /// The state of the symlink of each config file deployed as one.
+    links: Vec<Option<LinkStatus>>,
+    /// The alternatives of the draft in the order they were added, which may
+    /// repeat a distribution while it is being typed.
+    alternatives: Vec<(String, Source)>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Name(String),
+    Description(String),
+    Enabled(bool),
+    Kind(usize),
+    SourceName(String),
+    FlatpakVersion(String),
+    /// The version of sources that can pin one.
+    Version(String),
+    CargoFeatures(String),
+    CargoLocked(bool),
+    CargoGit(String),
+    RustupTargets(String),
+    RustupComponents(String),
+    PythonExtras(String),
+    AddAlternative,
+    RemoveAlternative(usize),
+    AlternativeDistro(usize, String),
+    AlternativeKind(usize, usize),
+    AlternativeName(usize, String),
+    Page(usize),
+    AddConfigFile,
+    ImportFiles,
+    /// Config files read from disk, to add to the package.
+    Imported(Vec<ConfigFile>),
+    RemoveConfigFile(usize),
+    ConfigExtension(usize, String),
+    ConfigTarget(usize, String),
+    ConfigMode(usize, String),
+    ConfigLinked(usize, bool),
+    ConfigTemplate(usize, bool),
+    ConfigMerged(usize, bool),
+    ConfigContent(usize, text_editor::Action),
+    Duplicate,
+    Delete,
+    Save,
+    Cancel,
+}
+
+#[derive(Debug, Clone)]
+pub enum Command {
+    /// Asks the user for files to add as config files.
+    ImportFiles,
+    Duplicate(Uuid),
+    Delete(Uuid),
+    Save(Package),
+    Cancel,
+}
+
+impl PackageEditor {
+    /// An editor for a new package on `page`, installed with `kind`.
+    pub fn new(page: Page, kind: Kind) -> Self {
+        let mut editor = Self::edit(Package::new("", kind.source(""), page));
+        editor.new = true;
+        editor
+    }
+
+    /// An editor for an existing package.
+    pub fn edit(package: Package) -> Self {
+        let contents = package
+            .config
+            .iter()
+            .map(|file| text_editor::Content::with_text(&file.content))
+            .collect();
+
+        let alternatives = package
+            .alternatives
+            .iter()
+            .map(|(distro, source)| (distro.clone(), source.clone()))
+            .collect();
+
+        let mut editor = Self {
+            errors: package.validate(),
+            links: vec![],
+            alternatives,
+            draft: package,
+            new: false,
+            submitted: false,
+            kinds: Kind::ALL.iter().map(ToString::to_string).collect(),
+            pages: Page::ALL.iter().map(ToString::to_string).collect(),
+            contents,
+        };
+
+        editor.refresh_links();
+        editor
+    }
+
+    /// The id of the package being edited.
+    pub fn id(&self) -> Uuid {
+        self.draft.id
+    }
+
+    pub fn view(&self) -> Element<Message> {
+        let spacing = cosmic::theme::active().cosmic().spacing;
+        let package = &self.draft;
+
+        let mut details = widget::settings::view_section(fl!("package"))
+            .add(widget::settings::item_row(vec![widget::text_input(
+                fl!("name"),
+                package.name.clone(),
+            )
+            .label(fl!("name"))
+            .on_input(Message::Name)
+            .into()]))
+            .add(widget::settings::item_row(vec![widget::text_input(
+                fl!("description"),
+                package.description.clone(),
+            )
+            .label(fl!("description"))
+            .on_input(Message::Description)
+            .into()]))
+            .add(widget::settings::item(
+                fl!("page"),
+                widget::dropdown(
+                    &self.pages,
+                    Page::ALL.iter().position(|page| *page == package.page),
+                    Message::Page,
+                ),
+            ))
+            .add(widget::settings::item(
+                fl!("enabled"),
+                widget::checkbox("", package.enabled, Message::Enabled),
+            ));
+        details = self.add_error(details, Invalid::Name);
+
+        let mut source = widget::settings::view_section(fl!("source"))
+            .add(widget::settings::item(
+                fl!("package-manager"),
+                widget::dropdown(
+                    &self.kinds,
+                    Kind::ALL
+                        .iter()
+                        .position(|kind| *kind == package.source.kind()),
+                    Message::Kind,
+                ),
+            ))
+            .add(widget::settings::item_row(vec![widget::text_input(
+                fl!("package-name"),
+                package.source.name().to_string(),
+            )
+            .label(fl!("package-name"))
+            .on_input(Message::SourceName)
+            .into()]));
+
+        if let Source::Flatpak { version, .. } = &package.source {
+            source = source.add(widget::settings::item_row(vec![widget::text_input(
+                fl!("flatpak-branch"),
+                version.clone(),
+            )
+            .label(fl!("flatpak-branch"))
+            .on_input(Message::FlatpakVersion)
+            .into()]));
+        }
+        if let Some(version) = package.source.version() {
+            source = source.add(widget::settings::item_row(vec![widget::text_input(
+                fl!("latest-version"),
+                version.to_string(),
+            )
+            .label(fl!("version"))
+            .on_input(Message::Version)
+            .into()]));
+            source = self.add_error(source, Invalid::Version);
+        }
+        if let Source::Cargo {
+            features,
+            locked,
+            git,
+            ..
+        } = &package.source
+        {
+            source = source.add(widget::settings::item_row(vec![widget::text_input(
+                fl!("features-placeholder"),
+                features.join(", "),
+            )
+            .label(fl!("features"))
+            .on_input(Message::CargoFeatures)
+            .into()]));
+            source = self.add_error(source, Invalid::Features);
+            source = source.add(widget::settings::item_row(vec![widget::text_input(
+                fl!("crates-io"),
+                git.clone(),
+            )
+            .label(fl!("git-repository"))
+            .on_input(Message::CargoGit)
+            .into()]));
+            source = self.add_error(source, Invalid::Git);
+            source = source.add(widget::settings::item(
+                fl!("cargo-locked"),
+                widget::checkbox("", *locked, Message::CargoLocked),
+            ));
+        }
+        if let Source::Rustup {
+            targets,
+            components,
+            ..
+        } = &package.source
+        {
+            source = source.add(widget::settings::item_row(vec![widget::text_input(
+                fl!("rustup-targets-placeholder"),
+                targets.join(", "),
+            )
+            .label(fl!("rustup-targets"))
+            .on_input(Message::RustupTargets)
+            .into()]));
+            source = self.add_error(source, Invalid::Targets);
+            source = source.add(widget::settings::item_row(vec![widget::text_input(
+                fl!("rustup-components-placeholder"),
+                components.join(", "),
+            )
+            .label(fl!("rustup-components"))
+            .on_input(Message::RustupComponents)
+            .into()]));
+            source = self.add_error(source, Invalid::Components);
+        }
+        if let Source::Pipx { extras, .. } | Source::Uv { extras, .. } = &package.source {
+            source = source.add(widget::settings::item_row(vec![widget::text_input(
+                fl!("python-extras-placeholder"),
+                extras.join(", "),
+            )
+            .label(fl!("python-extras"))
+            .on_input(Message::PythonExtras)
+            .into()]));
+            source = self.add_error(source, Invalid::Extras);
+        }
+        source = self.add_error(source, Invalid::SourceName);
+
+        let mut alternatives = widget::settings::view_section(fl!("alternatives"))
+            .add(widget::text::caption(fl!("alternatives-desc")));
+        for (index, (distro, source)) in self.alternatives.iter().enumerate() {
+            alternatives = alternatives.add(widget::settings::item_row(vec![
+                widget::text_input(fl!("distribution"), distro.clone())
+                    .on_input(move |distro| Message::AlternativeDistro(index, distro))
+                    .width(Length::FillPortion(2))
+                    .into(),
+                widget::dropdown(
+                    &self.kinds,
+                    Kind::ALL.iter().position(|kind| *kind == source.kind()),
+                    move |kind| Message::AlternativeKind(index, kind),
+                )
+                .into(),
+                widget::text_input(fl!("package-name"), source.name().to_string())
+                    .on_input(move |name| Message::AlternativeName(index, name))
+                    .width(Length::FillPortion(3))
+                    .into(),
+                widget::button::icon(icon::from_name("user-trash-symbolic"))
+                    .on_press(Message::RemoveAlternative(index))
+                    .into(),
+            ]));
+        }
+        if self.submitted {
+            for error in &self.errors {
+                if let Invalid::Alternative(_) | Invalid::DuplicateAlternative(_) = error {
+                    alternatives = alternatives.add(widget::text::caption(error.to_string()));
+                }
+            }
+        }
+
+        let mut files = widget::settings::view_section(fl!("config-files"));
+        for (index, (file, content)) in package.config.iter().zip(&self.contents).enumerate() {
+            let header = widget::row()
+                .push(widget::text::heading(fl!(
+                    "config-file",
+                    index = (index + 1)
+                )))
+                .push(widget::horizontal_space(Length::Fill))
+                .push(
+                    widget::button::icon(icon::from_name("user-trash-symbolic"))
+                        .on_press(Message::RemoveConfigFile(index)),
+                )
+                .align_items(Alignment::Center);
+
+            let file = widget::column()
+                .push(header)
+                .push(
+                    widget::text_input(fl!("target"), file.target.display().to_string())
+                        .label(fl!("target"))
+                        .on_input(move |target| Message::ConfigTarget(index, target)),
+                )
+                .push(
+                    widget::text_input(fl!("extension"), file.extension.clone())
+                        .label(fl!("extension"))
+                        .on_input(move |extension| Message::ConfigExtension(index, extension)),
+                )
+                .push(
+                    widget::text_input(
+                        fl!("file-mode-placeholder"),
+                        file.mode
+                            .map(|mode| format!("{mode:o}"))
+                            .unwrap_or_default(),
+                    )
+                    .label(fl!("file-mode"))
+                    .on_input(move |mode| Message::ConfigMode(index, mode)),
+                )
+                .push(
+                    widget::row()
+                        .push(widget::checkbox(
+                            fl!("link-file"),
+                            file.method == DeployMethod::Symlink,
+                            move |linked| Message::ConfigLinked(index, linked),
+                        ))
+                        .push(widget::horizontal_space(Length::Fill))
+                        .push_maybe(
+                            self.links
+                                .get(index)
+                                .copied()
+                                .flatten()
+                                .map(|status| widget::text::caption(link_status(status))),
+                        )
+                        .align_items(Alignment::Center),
+                )
+                .push(widget::checkbox(
+                    fl!("merge-file"),
+                    file.method == DeployMethod::Merge,
+                    move |merged| Message::ConfigMerged(index, merged),
+                ))
+                .push(widget::checkbox(
+                    fl!("template-file"),
+                    file.template,
+                    move |template| Message::ConfigTemplate(index, template),
+                ))
+                .push(
+                    text_editor(content)
+                        .on_action(move |action| Message::ConfigContent(index, action))
+                        .height(Length::Fixed(160.0)),
+                )
+                .spacing(spacing.space_xxs);
+
+            files = files.add(file);
+            files = self.add_error(files, Invalid::Target(index));
+            files = self.add_error(files, Invalid::MergeFormat(index));
+        }
+
+        let mut actions = widget::row()
+            .push(
+                widget::button::standard(fl!("add-alternative")).on_press(Message::AddAlternative),
+            )
+            .push(widget::button::standard(fl!("add-config-file")).on_press(Message::AddConfigFile))
+            .push(widget::button::standard(fl!("import-files")).on_press(Message::ImportFiles))
+            .push(widget::horizontal_space(Length::Fill));
+        if !self.new {
+            actions = actions
+                .push(widget::button::destructive(fl!("delete-package")).on_press(Message::Delete))
+                .push(
+                    widget::button::standard(fl!("duplicate-package")).on_press(Message::Duplicate),
+                );
+        }
+        let actions = actions
+            .push(widget::button::standard(fl!("cancel")).on_press(Message::Cancel))
+            .push(
+                widget::button::suggested(if self.new { fl!("create") } else { fl!("save") })
+                    .on_press(Message::Save),
+            )
+            .spacing(spacing.space_xxs);
+
+        widget::column()
+            .push(details)
+            .push(source)
+            .push(alternatives)
+            .push(files)
+            .push(actions)
+            .spacing(spacing.space_s)
+            .into()
+    }
+
+    /// Shows `error` at the end of `section` once the user tried to save.
+    fn add_error<'a>(
+        &self,
+        section: widget::settings::Section<'a, Message>,
+        error: Invalid,
+    ) -> widget::settings::Section<'a, Message> {
+        if self.submitted && self.errors.contains(&error) {
+            section.add(widget::text::caption(error.to_string()))
+        } else {
+            section
+        }
+    }
+
+    pub fn update(&mut self, message: Message) -> Vec<Command> {
+        let mut commands = vec![];
+        match message {
+            Message::Name(name) => self.draft.name = name,
+            Message::Description(description) => self.draft.description = description,
+            Message::Enabled(enabled) => self.draft.enabled = enabled,
+            Message::Kind(index) => {
+                if let Some(kind) = Kind::ALL.get(index) {
+                    self.draft.source = kind.source(self.draft.source.name());
+                }
+            }
+            Message::SourceName(name) => self.draft.source.set_name(name),
+            Message::FlatpakVersion(branch) => {
+                if let Source::Flatpak { version, .. } = &mut self.draft.source {
+                    *version = branch;
+                }
+            }
+            Message::Version(new) => {
+                if let Some(version) = self.draft.source.version_mut() {
+                    *version = new.trim().to_string();
+                }
+            }
+            Message::CargoFeatures(list) => {
+                if let Source::Cargo { features, .. } = &mut self.draft.source {
+                    *features = split_list(&list);
+                }
+            }
+            Message::CargoLocked(new) => {
+                if let Source::Cargo { locked, .. } = &mut self.draft.source {
+                    *locked = new;
+                }
+            }
+            Message::CargoGit(url) => {
+                if let Source::Cargo { git, .. } = &mut self.draft.source {
+                    *git = url.trim().to_string();
+                }
+            }
+            Message::RustupTargets(list) => {
+                if let Source::Rustup { targets, .. } = &mut self.draft.source {
+                    *targets = split_list(&list);
+                }
+            }
+            Message::RustupComponents(list) => {
+                if let Source::Rustup { components, .. } = &mut self.draft.source {
+                    *components = split_list(&list);
+                }
+            }
+            Message::PythonExtras(list) => {
+                if let Source::Pipx { extras, .. } | Source::Uv { extras, .. } =
+                    &mut self.draft.source
+                {
+                    *extras = split_list(&list);
+                }
+            }
+            Message::AddAlternative => {
+                let kind = self.draft.source.kind();
+                self.alternatives
+                    .push((String::new(), kind.source(self.draft.source.name())));
+            }
+            Message::RemoveAlternative(index) => {
+                if index < self.alternatives.len() {
+                    self.alternatives.remove(index);
+                }
+            }
+            Message::AlternativeDistro(index, distro) => {
+                if let Some(alternative) = self.alternatives.get_mut(index) {
+                    alternative.0 = distro.trim().to_lowercase();
+                }
+            }
+            Message::AlternativeKind(index, kind) => {
+                if let (Some(alternative), Some(kind)) =
+                    (self.alternatives.get_mut(index), Kind::ALL.get(kind))
+                {
+                    alternative.1 = kind.source(alternative.1.name());
+                }
+            }
+            Message::AlternativeName(index, name) => {
+                if let Some(alternative) = self.alternatives.get_mut(index) {
+                    alternative.1.set_name(name);
+                }
+            }
+            Message::Page(index) => {
+                if let Some(page) = Page::ALL.get(index) {
+                    self.draft.page = page.clone();
+                }
+            }
+            Message::AddConfigFile => {
+                self.draft.config.push(ConfigFile::new(PathBuf::new(), ""));
+                self.contents.push(text_editor::Content::new());
+            }
+            Message::ImportFiles => commands.push(Command::ImportFiles),
+            Message::Imported(files) => {
+                for file in files {
+                    self.contents
+                        .push(text_editor::Content::with_text(&file.content));
+                    self.draft.config.push(file);
+                }
+            }
+            Message::RemoveConfigFile(index) => {
+                if index < self.draft.config.len() {
+                    self.draft.config.remove(index);
+                    self.contents.remove(index);
+                }
+            }
+            Message::ConfigExtension(index, extension) => {
+                if let Some(file) = self.draft.config.get_mut(index) {
+                    file.extension = extension;
+                }
+            }
+            Message::ConfigTarget(index, target) => {
+                if let Some(file) = self.draft.config.get_mut(index) {
+                    file.target = PathBuf::from(target);
+                }
+            }
+            Message::ConfigMode(index, mode) => {
+                if let Some(file) = self.draft.config.get_mut(index) {
+                    if mode.is_empty() {
+                        file.mode = None;
+                    } else if let Ok(mode) = u32::from_str_radix(&mode, 8) {
+                        // Only permission bits, anything else is a typo.
+                        if mode <= 0o7777 {
+                            file.mode = Some(mode);
+                        }
+                    }
+                }
+            }
+            Message::ConfigLinked(index, linked) => {
+                if let Some(file) = self.draft.config.get_mut(index) {
+                    file.method = if linked {
+                        DeployMethod::Symlink
+                    } else {
+                        DeployMethod::Copy
+                    };
+                }
+            }
+            Message::ConfigMerged(index, merged) => {
+                if let Some(file) = self.draft.config.get_mut(index) {
+                    file.method = if merged {
+                        DeployMethod::Merge
+                    } else {
+                        DeployMethod::Copy
+                    };
+                }
+            }
+            Message::ConfigTemplate(index, template) => {
+                if let Some(file) = self.draft.config.get_mut(index) {
+                    file.template = template;
+                }
+            }
+            Message::ConfigContent(index, action) => {
+                if let (Some(file), Some(content)) = (
+                    self.draft.config.get_mut(index),
+                    self.contents.get_mut(index),
+                ) {
+                    content.perform(action);
+                    file.content = content.text();
+                }
+            }
+            Message::Duplicate => commands.push(Command::Duplicate(self.draft.id)),
+            Message::Delete => commands.push(Command::Delete(self.draft.id)),
+            Message::Save => {
+                self.submitted = true;
+                if self.errors.is_empty() {
+                    let mut package = self.draft.clone();
+                    drop_empty_entries(&mut package.source);
+                    commands.push(Command::Save(package));
+                }
+            }
+            Message::Cancel => commands.push(Command::Cancel),
+        }
+
+        self.draft.alternatives = self.alternatives.iter().cloned().collect();
+        self.errors = self.validate();
+        self.refresh_links();
+        commands
+    }
+
+    /// Checks the symlinks of the config files deployed as one.
+    fn refresh_links(&mut self) {
+        self.links = self
+            .draft
+            .config
+            .iter()
+            .map(|file| {
+                let target = file.target_path();
+                match file.method {
+                    DeployMethod::Copy | DeployMethod::Merge => None,
+                    DeployMethod::Symlink => deploy::managed_path(self.draft.id, &target)
+                        .map(|managed| deploy::link_status(&target, &managed)),
+                }
+            })
+            .collect();
+    }
+
+    /// The problems of the draft as it would be saved, including alternatives that
+    /// repeat a distribution.
+    fn validate(&self) -> Vec<Invalid> {
+        let mut draft = self.draft.clone();
+        drop_empty_entries(&mut draft.source);
+        let mut errors = draft.validate();
+
+        for (index, (distro, _)) in self.alternatives.iter().enumerate() {
+            let repeated = self.alternatives[..index]
+                .iter()
+                .any(|(other, _)| other == distro);
+            let error = Invalid::DuplicateAlternative(distro.clone());
+            if repeated && !distro.is_empty() && !errors.contains(&error) {
+                errors.push(error);
+            }
+        }
+
+        errors
+    }
+}
+
+fn link_status(status: LinkStatus) -> String {
+    match status {
+        LinkStatus::Linked => fl!("link-linked"),
+        LinkStatus::Missing => fl!("link-missing"),
+        LinkStatus::Broken => fl!("link-broken"),
+        LinkStatus::Replaced => fl!("link-replaced"),
+    }
+}
+
+/// Splits a comma-separated list typed into a text input. Empty entries are kept so
+/// a trailing comma survives while typing, and dropped on save.
+fn split_list(list: &str) -> Vec<String> {
+    if list.trim().is_empty() {
+        return vec![];
+    }
+    list.split(',')
+        .map(|item| item.trim().to_string())
+        .collect()
+}
+
+/// Drops the empty entries `split_list` keeps while typing from the lists of `source`.
+fn drop_empty_entries(source: &mut Source) {
+    match source {
+        Source::Cargo { features, .. } => features.retain(|f| !f.is_empty()),
+        Source::Pipx { extras, .. } | Source::Uv { extras, .. } => {
+            extras.retain(|extra| !extra.is_empty())
+        }
+        Source::Rustup {
+            targets,
+            components,
+            ..
+        } => {
+            targets.retain(|target| !target.is_empty());
+            components.retain(|component| !component.is_empty());
+        }
+        _ => {}
+    }
+}
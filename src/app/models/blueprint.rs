@@ -0,0 +1,220 @@
+// SPDX-License-Identifier: GPL-3.0
+
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::package::Package;
+
+/// The newest blueprint format this version of Blueprint understands.
+pub const VERSION: u32 = 1;
+
+/// A self-contained blueprint that can be committed to a repository and shared.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Blueprint {
+    /// The format version the document was written with.
+    pub version: u32,
+    #[serde(default = "Uuid::new_v4")]
+    pub id: Uuid,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub packages: Vec<Package>,
+    /// Variables the templated config files of the packages can use.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variables: Vec<Variable>,
+}
+
+/// A value each user of a blueprint fills in, such as their email address.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Variable {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    /// The value used when the user gives none. Empty if one must be given.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub default: String,
+}
+
+/// The file formats a blueprint can be stored in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Ron,
+    Toml,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(PathBuf, std::io::Error),
+    UnknownFormat(PathBuf),
+    UnsupportedVersion(u32),
+    Parse(String),
+    Serialize(String),
+}
+
+impl Blueprint {
+    pub fn new(name: &str, packages: Vec<Package>) -> Self {
+        Self {
+            version: VERSION,
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            description: String::new(),
+            packages,
+            variables: vec![],
+        }
+    }
+
+    /// Reads a blueprint, picking the format from the file extension.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let format = Format::from_path(path)?;
+        let contents =
+            std::fs::read_to_string(path).map_err(|err| Error::Io(path.to_path_buf(), err))?;
+        Self::parse(&contents, format)
+    }
+
+    /// Writes the blueprint, picking the format from the file extension.
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let contents = self.serialize(Format::from_path(path)?)?;
+        std::fs::write(path, contents).map_err(|err| Error::Io(path.to_path_buf(), err))
+    }
+
+    pub fn parse(contents: &str, format: Format) -> Result<Self, Error> {
+        let blueprint: Self = match format {
+            Format::Ron => ron::from_str(contents).map_err(|err| Error::Parse(err.to_string()))?,
+            Format::Toml => {
+                toml::from_str(contents).map_err(|err| Error::Parse(err.to_string()))?
+            }
+        };
+
+        if blueprint.version > VERSION {
+            return Err(Error::UnsupportedVersion(blueprint.version));
+        }
+
+        Ok(blueprint)
+    }
+
+    pub fn serialize(&self, format: Format) -> Result<String, Error> {
+        match format {
+            Format::Ron => ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+                .map_err(|err| Error::Serialize(err.to_string())),
+            Format::Toml => {
+                toml::to_string_pretty(self).map_err(|err| Error::Serialize(err.to_string()))
+            }
+        }
+    }
+}
+
+impl Format {
+    pub const EXTENSIONS: [&'static str; 2] = ["ron", "toml"];
+
+    pub fn from_path(path: &Path) -> Result<Self, Error> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("ron") => Ok(Self::Ron),
+            Some("toml") => Ok(Self::Toml),
+            _ => Err(Error::UnknownFormat(path.to_path_buf())),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(path, err) => write!(f, "{}: {err}", path.display()),
+            Error::UnknownFormat(path) => write!(
+                f,
+                "{}: unknown blueprint format, expected a .ron or .toml file",
+                path.display()
+            ),
+            Error::UnsupportedVersion(version) => write!(
+                f,
+                "blueprint version {version} is newer than the supported version {VERSION}"
+            ),
+            Error::Parse(err) => write!(f, "invalid blueprint: {err}"),
+            Error::Serialize(err) => write!(f, "failed to write blueprint: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::models::package::{ConfigFile, DeployMethod, Source};
+    use crate::app::Page;
+
+    fn blueprint() -> Blueprint {
+        let mut fish = Package::new("Fish", Source::Apt("fish".into()), Page::Shells);
+        fish.alternatives
+            .insert("arch".into(), Source::Pacman("fish".into()));
+        fish.config.push(ConfigFile {
+            mode: Some(0o600),
+            method: DeployMethod::Symlink,
+            template: true,
+            ..ConfigFile::new(
+                "$XDG_CONFIG_HOME/fish/config.fish",
+                "set -g fish_greeting {{ greeting }}\n",
+            )
+        });
+        let rust = Package::new(
+            "Rust",
+            Source::Rustup {
+                toolchain: "stable".into(),
+                targets: vec!["wasm32-unknown-unknown".into()],
+                components: vec!["rust-analyzer".into()],
+            },
+            Page::Shells,
+        );
+
+        let mut blueprint = Blueprint::new("Test", vec![fish, rust]);
+        blueprint.description = "Shells and Rust.".into();
+        blueprint.variables.push(Variable {
+            name: "greeting".into(),
+            description: "Printed when fish starts.".into(),
+            default: "hello".into(),
+        });
+        blueprint
+    }
+
+    #[test]
+    fn the_sample_blueprint_loads() {
+        let sample = Blueprint::parse(include_str!("../../../data/sample.ron"), Format::Ron)
+            .expect("the sample blueprint should parse");
+
+        assert_eq!(sample.version, VERSION);
+        assert_eq!(sample.name, "Sample");
+        assert!(!sample.packages.is_empty());
+    }
+
+    #[test]
+    fn saving_and_loading_keeps_the_blueprint() {
+        let dir = tempfile::tempdir().unwrap();
+        let blueprint = blueprint();
+
+        for extension in Format::EXTENSIONS {
+            let path = dir.path().join(format!("test.{extension}"));
+            blueprint.save(&path).unwrap();
+            let loaded = Blueprint::load(&path).unwrap();
+
+            assert_eq!(loaded, blueprint, "{extension}");
+        }
+    }
+
+    #[test]
+    fn newer_versions_are_refused() {
+        let newer = format!("(version: {}, name: \"Newer\")", VERSION + 1);
+
+        assert!(matches!(
+            Blueprint::parse(&newer, Format::Ron),
+            Err(Error::UnsupportedVersion(version)) if version == VERSION + 1
+        ));
+        assert!(matches!(
+            Blueprint::parse(&format!("version = {}\n", VERSION + 1), Format::Toml),
+            Err(Error::UnsupportedVersion(_))
+        ));
+    }
+}
@@ -1,23 +1,78 @@
 // SPDX-License-Identifier: GPL-3.0
 
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
 
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::app::Page;
+use crate::engine::host::OsRelease;
+use crate::engine::merge::Format;
+use crate::fl;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Package {
+    #[serde(default = "Uuid::new_v4")]
     pub id: Uuid,
     pub name: String,
+    #[serde(default)]
     pub description: String,
     pub source: Source,
+    /// Sources to use instead of `source`, keyed by os-release distribution id.
+    #[serde(
+        default,
+        skip_serializing_if = "BTreeMap::is_empty",
+        deserialize_with = "unique_alternatives"
+    )]
+    pub alternatives: BTreeMap<String, Source>,
+    #[serde(default)]
     pub config: Vec<ConfigFile>,
     pub page: Page,
+    #[serde(default = "enabled")]
     pub enabled: bool,
 }
 
+fn enabled() -> bool {
+    true
+}
+
+/// Reads the alternatives of a package, refusing a distribution given twice rather
+/// than keeping the last one.
+fn unique_alternatives<'de, D>(deserializer: D) -> Result<BTreeMap<String, Source>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    struct Alternatives;
+
+    impl<'de> serde::de::Visitor<'de> for Alternatives {
+        type Value = BTreeMap<String, Source>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "a map from distribution ids to sources")
+        }
+
+        fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+        where
+            A: serde::de::MapAccess<'de>,
+        {
+            let mut alternatives = BTreeMap::new();
+            while let Some((distro, source)) = map.next_entry::<String, Source>()? {
+                if alternatives.contains_key(&distro) {
+                    let error = Invalid::DuplicateAlternative(distro);
+                    return Err(serde::de::Error::custom(error));
+                }
+                alternatives.insert(distro, source);
+            }
+            Ok(alternatives)
+        }
+    }
+
+    deserializer.deserialize_map(Alternatives)
+}
+
 impl Package {
     pub fn new(name: &str, source: Source, page: Page) -> Self {
         Self {
@@ -25,18 +80,249 @@ impl Package {
             name: name.to_string(),
             description: String::new(),
             source,
+            alternatives: BTreeMap::new(),
             config: vec![],
             page,
             enabled: true,
         }
     }
+
+    /// The source to install the package from on `os`: the alternative for its
+    /// distribution or the closest one it is derived from, otherwise `source`.
+    pub fn source_for(&self, os: &OsRelease) -> &Source {
+        os.ids()
+            .find_map(|id| self.alternatives.get(id))
+            .unwrap_or(&self.source)
+    }
+
+    /// Checks that every field needed to apply the package is filled in.
+    pub fn validate(&self) -> Vec<Invalid> {
+        let mut errors = vec![];
+
+        if self.name.trim().is_empty() {
+            errors.push(Invalid::Name);
+        }
+
+        if !self.source.has_valid_name() {
+            errors.push(Invalid::SourceName);
+        }
+
+        if let Source::Cargo { version, .. }
+        | Source::Npm { version, .. }
+        | Source::Pnpm { version, .. }
+        | Source::Go { version, .. } = &self.source
+        {
+            if !version.is_empty() && !is_argument(version) {
+                errors.push(Invalid::Version);
+            }
+        }
+
+        if let Source::Cargo { features, git, .. } = &self.source {
+            if !features.iter().all(|feature| is_argument(feature)) {
+                errors.push(Invalid::Features);
+            }
+            if !git.is_empty() && !is_argument(git) {
+                errors.push(Invalid::Git);
+            }
+        }
+
+        if let Source::Rustup {
+            targets,
+            components,
+            ..
+        } = &self.source
+        {
+            if !targets.iter().all(|target| is_argument(target)) {
+                errors.push(Invalid::Targets);
+            }
+            if !components.iter().all(|component| is_argument(component)) {
+                errors.push(Invalid::Components);
+            }
+        }
+
+        if let Source::Pipx { extras, .. } | Source::Uv { extras, .. } = &self.source {
+            if !extras.iter().all(|extra| is_argument(extra)) {
+                errors.push(Invalid::Extras);
+            }
+        }
+
+        for (distro, source) in &self.alternatives {
+            if distro.trim().is_empty() || !source.has_valid_name() {
+                errors.push(Invalid::Alternative(distro.clone()));
+            }
+        }
+
+        for (index, file) in self.config.iter().enumerate() {
+            if file.target.as_os_str().is_empty() {
+                errors.push(Invalid::Target(index));
+            }
+            if file.method == DeployMethod::Merge
+                && Format::from_extension(&file.extension).is_none()
+            {
+                errors.push(Invalid::MergeFormat(index));
+            }
+        }
+
+        errors
+    }
+}
+
+/// A problem that prevents a package from being applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Invalid {
+    Name,
+    SourceName,
+    /// The version starts with a dash or contains whitespace.
+    Version,
+    /// A crate feature is empty, starts with a dash or contains whitespace.
+    Features,
+    /// The git repository of a crate starts with a dash or contains whitespace.
+    Git,
+    /// A Rust target is empty, starts with a dash or contains whitespace.
+    Targets,
+    /// A Rust component is empty, starts with a dash or contains whitespace.
+    Components,
+    /// An extra dependency of a Python application is empty, starts with a dash or
+    /// contains whitespace.
+    Extras,
+    /// The alternative for this distribution has no distribution or package name.
+    Alternative(String),
+    /// More than one alternative was given for this distribution. Reported by the
+    /// editor and when reading a blueprint, since the model cannot hold both.
+    DuplicateAlternative(String),
+    /// The config file at this index has no target.
+    Target(usize),
+    /// The config file at this index is merged but its extension is not a format
+    /// that can be merged.
+    MergeFormat(usize),
+}
+
+impl Display for Invalid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Invalid::Name => write!(f, "{}", fl!("invalid-name")),
+            Invalid::SourceName => write!(f, "{}", fl!("invalid-source-name")),
+            Invalid::Version => write!(f, "{}", fl!("invalid-version")),
+            Invalid::Features => write!(f, "{}", fl!("invalid-features")),
+            Invalid::Git => write!(f, "{}", fl!("invalid-git")),
+            Invalid::Targets => write!(f, "{}", fl!("invalid-targets")),
+            Invalid::Components => write!(f, "{}", fl!("invalid-components")),
+            Invalid::Extras => write!(f, "{}", fl!("invalid-extras")),
+            Invalid::Alternative(distro) => {
+                write!(f, "{}", fl!("invalid-alternative", distro = distro.clone()))
+            }
+            Invalid::DuplicateAlternative(distro) => write!(
+                f,
+                "{}",
+                fl!("duplicate-alternative", distro = distro.clone())
+            ),
+            Invalid::Target(index) => {
+                write!(f, "{}", fl!("invalid-target", index = (index + 1)))
+            }
+            Invalid::MergeFormat(index) => {
+                write!(f, "{}", fl!("invalid-merge-format", index = (index + 1)))
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct ConfigFile {
-    content: String,
-    extension: String,
-    target: PathBuf,
+    pub content: String,
+    pub extension: String,
+    /// Where the file is written. May start with `~`, `$HOME` or an XDG base
+    /// directory such as `$XDG_CONFIG_HOME`.
+    pub target: PathBuf,
+    /// The permissions of the written file, such as `0o600`. Left as is when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>,
+    #[serde(default)]
+    pub method: DeployMethod,
+    /// Whether `content` has `{{ variable }}` placeholders to fill in when the file
+    /// is written.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub template: bool,
+}
+
+/// How a config file is put in place.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum DeployMethod {
+    /// `content` is written to the target.
+    #[default]
+    Copy,
+    /// `content` is written to a copy managed by Blueprint, which the target links
+    /// to, so edits made through the link flow back into the blueprint.
+    Symlink,
+    /// `content` is a partial JSON, TOML, INI or YAML document deep-merged into the
+    /// target, keeping the settings it already has.
+    Merge,
+}
+
+impl ConfigFile {
+    /// A config file copied to `target` with `content`. The extension is taken from
+    /// the file name, such as `bashrc` for `.bashrc`.
+    pub fn new(target: impl Into<PathBuf>, content: &str) -> Self {
+        let target = target.into();
+        let extension = target
+            .extension()
+            .or_else(|| target.file_name())
+            .map(|name| name.to_string_lossy().trim_start_matches('.').to_string())
+            .unwrap_or_default();
+
+        Self {
+            content: content.to_string(),
+            extension,
+            target,
+            mode: None,
+            method: DeployMethod::Copy,
+            template: false,
+        }
+    }
+
+    /// The target path with a leading `~`, `$HOME` or XDG base directory expanded.
+    pub fn target_path(&self) -> PathBuf {
+        let mut components = self.target.components();
+        let Some(first) = components.next() else {
+            return self.target.clone();
+        };
+
+        let base = match first.as_os_str().to_str() {
+            Some("~" | "$HOME" | "${HOME}") => dirs::home_dir(),
+            Some("$XDG_CONFIG_HOME" | "${XDG_CONFIG_HOME}") => dirs::config_dir(),
+            Some("$XDG_DATA_HOME" | "${XDG_DATA_HOME}") => dirs::data_dir(),
+            Some("$XDG_STATE_HOME" | "${XDG_STATE_HOME}") => dirs::state_dir(),
+            Some("$XDG_CACHE_HOME" | "${XDG_CACHE_HOME}") => dirs::cache_dir(),
+            _ => None,
+        };
+
+        match base {
+            Some(base) => base.join(components.as_path()),
+            None => self.target.clone(),
+        }
+    }
+
+    /// A config file with the contents of the file at `path`, put back in the same
+    /// place.
+    ///
+    /// The target is made relative to `$XDG_CONFIG_HOME` or `~` when the file is in
+    /// one of them, and permissions other than `644` are kept.
+    pub fn import(path: &Path) -> std::io::Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let mode = std::fs::metadata(path)?.permissions().mode() & 0o7777;
+
+        let relative = |base: Option<PathBuf>, prefix: &str| {
+            let relative = path.strip_prefix(base?).ok()?;
+            Some(Path::new(prefix).join(relative))
+        };
+        let target = relative(dirs::config_dir(), "$XDG_CONFIG_HOME")
+            .or_else(|| relative(dirs::home_dir(), "~"))
+            .unwrap_or_else(|| path.to_path_buf());
+
+        Ok(Self {
+            mode: (mode != 0o644).then_some(mode),
+            ..Self::new(target, &content)
+        })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -44,5 +330,396 @@ pub enum Source {
     Apt(String),
     Dnf(String),
     Pacman(String),
-    Flatpak { id: String, version: String },
+    Zypper(String),
+    /// A Flathub application. `version` is the branch to install, such as `stable`,
+    /// and may be left empty to use the default one.
+    Flatpak {
+        id: String,
+        version: String,
+    },
+    /// A crate installed with `cargo install`, from crates.io unless `git` is given.
+    Cargo {
+        name: String,
+        /// The version to install, such as `14.1.0`. The latest one when empty.
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        version: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        features: Vec<String>,
+        /// Whether to build with the versions in the crate's `Cargo.lock`.
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        locked: bool,
+        /// A git repository to install the crate from instead of crates.io.
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        git: String,
+    },
+    /// A Rust toolchain installed with rustup, such as `stable` or `nightly-2024-05-01`,
+    /// with extra targets and components.
+    Rustup {
+        toolchain: String,
+        /// Targets to add, such as `wasm32-unknown-unknown`.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        targets: Vec<String>,
+        /// Components to add, such as `rust-analyzer`.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        components: Vec<String>,
+    },
+    /// A Python application installed into its own environment with pipx.
+    Pipx {
+        name: String,
+        /// A version such as `24.1.0`, or a specifier such as `>=24`. The latest
+        /// one when empty.
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        version: String,
+        /// More packages to install into the environment, with `pipx inject`.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        extras: Vec<String>,
+    },
+    /// A Python application installed into its own environment with `uv tool`.
+    Uv {
+        name: String,
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        version: String,
+        /// More packages to install into the environment, with `--with`.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        extras: Vec<String>,
+    },
+    /// A Node.js package installed globally with npm.
+    Npm {
+        name: String,
+        /// A version, range or tag, such as `5.4.5`, `^5` or `next`. The latest
+        /// version when empty.
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        version: String,
+    },
+    /// A Node.js package installed globally with pnpm.
+    Pnpm {
+        name: String,
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        version: String,
+    },
+    /// A Go program installed with `go install`, such as `golang.org/x/tools/gopls`.
+    Go {
+        package: String,
+        /// A module version such as `v0.15.3`. The latest one when empty.
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        version: String,
+    },
+}
+
+impl Source {
+    /// The name the package manager knows this source by.
+    pub fn name(&self) -> &str {
+        match self {
+            Source::Apt(name) | Source::Dnf(name) | Source::Pacman(name) | Source::Zypper(name) => {
+                name
+            }
+            Source::Flatpak { id, .. } => id,
+            Source::Cargo { name, .. }
+            | Source::Pipx { name, .. }
+            | Source::Uv { name, .. }
+            | Source::Npm { name, .. }
+            | Source::Pnpm { name, .. }
+            | Source::Go { package: name, .. } => name,
+            Source::Rustup { toolchain, .. } => toolchain,
+        }
+    }
+
+    /// Whether the name can be given to the package manager as is: it is not empty,
+    /// is not taken for an option and is a single argument.
+    pub fn has_valid_name(&self) -> bool {
+        is_argument(self.name())
+    }
+
+    pub fn set_name(&mut self, new: String) {
+        match self {
+            Source::Apt(name)
+            | Source::Dnf(name)
+            | Source::Pacman(name)
+            | Source::Zypper(name)
+            | Source::Cargo { name, .. }
+            | Source::Pipx { name, .. }
+            | Source::Uv { name, .. }
+            | Source::Npm { name, .. }
+            | Source::Pnpm { name, .. }
+            | Source::Go { package: name, .. }
+            | Source::Rustup {
+                toolchain: name, ..
+            } => *name = new,
+            Source::Flatpak { id, .. } => *id = new,
+        }
+    }
+
+    /// The version to install, for sources that can pin one. Flatpak branches are
+    /// not versions.
+    pub fn version(&self) -> Option<&str> {
+        match self {
+            Source::Cargo { version, .. }
+            | Source::Pipx { version, .. }
+            | Source::Uv { version, .. }
+            | Source::Npm { version, .. }
+            | Source::Pnpm { version, .. }
+            | Source::Go { version, .. } => Some(version),
+            _ => None,
+        }
+    }
+
+    pub fn version_mut(&mut self) -> Option<&mut String> {
+        match self {
+            Source::Cargo { version, .. }
+            | Source::Pipx { version, .. }
+            | Source::Uv { version, .. }
+            | Source::Npm { version, .. }
+            | Source::Pnpm { version, .. }
+            | Source::Go { version, .. } => Some(version),
+            _ => None,
+        }
+    }
+
+    pub fn kind(&self) -> Kind {
+        match self {
+            Source::Apt(_) => Kind::Apt,
+            Source::Dnf(_) => Kind::Dnf,
+            Source::Pacman(_) => Kind::Pacman,
+            Source::Zypper(_) => Kind::Zypper,
+            Source::Flatpak { .. } => Kind::Flatpak,
+            Source::Cargo { .. } => Kind::Cargo,
+            Source::Rustup { .. } => Kind::Rustup,
+            Source::Pipx { .. } => Kind::Pipx,
+            Source::Uv { .. } => Kind::Uv,
+            Source::Npm { .. } => Kind::Npm,
+            Source::Pnpm { .. } => Kind::Pnpm,
+            Source::Go { .. } => Kind::Go,
+        }
+    }
+}
+
+/// The package manager behind a [`Source`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    Apt,
+    Dnf,
+    Pacman,
+    Zypper,
+    Flatpak,
+    Cargo,
+    Rustup,
+    Pipx,
+    Uv,
+    Npm,
+    Pnpm,
+    Go,
+}
+
+impl Kind {
+    pub const ALL: [Kind; 12] = [
+        Kind::Apt,
+        Kind::Dnf,
+        Kind::Pacman,
+        Kind::Zypper,
+        Kind::Flatpak,
+        Kind::Cargo,
+        Kind::Rustup,
+        Kind::Pipx,
+        Kind::Uv,
+        Kind::Npm,
+        Kind::Pnpm,
+        Kind::Go,
+    ];
+
+    /// The executable of the package manager.
+    pub fn program(self) -> &'static str {
+        match self {
+            Kind::Apt => "apt-get",
+            Kind::Dnf => "dnf",
+            Kind::Pacman => "pacman",
+            Kind::Zypper => "zypper",
+            Kind::Flatpak => "flatpak",
+            Kind::Cargo => "cargo",
+            Kind::Rustup => "rustup",
+            Kind::Pipx => "pipx",
+            Kind::Uv => "uv",
+            Kind::Npm => "npm",
+            Kind::Pnpm => "pnpm",
+            Kind::Go => "go",
+        }
+    }
+
+    /// A source of this kind for the package called `name`.
+    pub fn source(self, name: &str) -> Source {
+        let name = name.to_string();
+        match self {
+            Kind::Apt => Source::Apt(name),
+            Kind::Dnf => Source::Dnf(name),
+            Kind::Pacman => Source::Pacman(name),
+            Kind::Zypper => Source::Zypper(name),
+            Kind::Flatpak => Source::Flatpak {
+                id: name,
+                version: String::new(),
+            },
+            Kind::Cargo => Source::Cargo {
+                name,
+                version: String::new(),
+                features: vec![],
+                locked: false,
+                git: String::new(),
+            },
+            Kind::Rustup => Source::Rustup {
+                toolchain: name,
+                targets: vec![],
+                components: vec![],
+            },
+            Kind::Pipx => Source::Pipx {
+                name,
+                version: String::new(),
+                extras: vec![],
+            },
+            Kind::Uv => Source::Uv {
+                name,
+                version: String::new(),
+                extras: vec![],
+            },
+            Kind::Npm => Source::Npm {
+                name,
+                version: String::new(),
+            },
+            Kind::Pnpm => Source::Pnpm {
+                name,
+                version: String::new(),
+            },
+            Kind::Go => Source::Go {
+                package: name,
+                version: String::new(),
+            },
+        }
+    }
+}
+
+impl Display for Kind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Kind::Apt => write!(f, "APT"),
+            Kind::Dnf => write!(f, "DNF"),
+            Kind::Pacman => write!(f, "Pacman"),
+            Kind::Zypper => write!(f, "Zypper"),
+            Kind::Flatpak => write!(f, "Flatpak"),
+            Kind::Cargo => write!(f, "Cargo"),
+            Kind::Rustup => write!(f, "Rustup"),
+            Kind::Pipx => write!(f, "pipx"),
+            Kind::Uv => write!(f, "uv"),
+            Kind::Npm => write!(f, "npm"),
+            Kind::Pnpm => write!(f, "pnpm"),
+            Kind::Go => write!(f, "Go"),
+        }
+    }
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::Apt(name) => write!(f, "apt:{name}"),
+            Source::Dnf(name) => write!(f, "dnf:{name}"),
+            Source::Pacman(name) => write!(f, "pacman:{name}"),
+            Source::Zypper(name) => write!(f, "zypper:{name}"),
+            Source::Flatpak { id, version } if version.is_empty() => write!(f, "flatpak:{id}"),
+            Source::Flatpak { id, version } => write!(f, "flatpak:{id}//{version}"),
+            Source::Cargo {
+                name, version, git, ..
+            } => {
+                write!(f, "cargo:{name}")?;
+                if !version.is_empty() {
+                    write!(f, "@{version}")?;
+                }
+                if !git.is_empty() {
+                    write!(f, " ({git})")?;
+                }
+                Ok(())
+            }
+            Source::Rustup {
+                toolchain,
+                targets,
+                components,
+            } => {
+                write!(f, "rustup:{toolchain}")?;
+                let extras: Vec<&str> = targets
+                    .iter()
+                    .chain(components)
+                    .map(String::as_str)
+                    .collect();
+                if !extras.is_empty() {
+                    write!(f, " ({})", extras.join(", "))?;
+                }
+                Ok(())
+            }
+            Source::Pipx {
+                name,
+                version,
+                extras,
+            }
+            | Source::Uv {
+                name,
+                version,
+                extras,
+            } => {
+                let program = self.kind().program();
+                write!(f, "{program}:{}", requirement(name, version))?;
+                if !extras.is_empty() {
+                    write!(f, " (with {})", extras.join(", "))?;
+                }
+                Ok(())
+            }
+            Source::Npm { name, version } | Source::Pnpm { name, version } => {
+                let program = self.kind().program();
+                write!(f, "{program}:{}", specifier(name, version))
+            }
+            Source::Go { package, version } => write!(f, "go:{}", specifier(package, version)),
+        }
+    }
+}
+
+/// Whether `value` can be given to a package manager as is: it is not empty, is not
+/// taken for an option and is a single argument.
+fn is_argument(value: &str) -> bool {
+    !value.is_empty() && !value.starts_with('-') && !value.contains(char::is_whitespace)
+}
+
+/// The pip requirement for the Python package `name` at `version`, which is taken
+/// as an exact version unless it starts with a comparison such as `>=`.
+pub fn requirement(name: &str, version: &str) -> String {
+    if version.is_empty() {
+        name.to_string()
+    } else if version.starts_with(['<', '>', '=', '!', '~']) {
+        format!("{name}{version}")
+    } else {
+        format!("{name}=={version}")
+    }
+}
+
+/// The npm package specifier for `name` at `version`, such as `typescript@^5`.
+pub fn specifier(name: &str, version: &str) -> String {
+    if version.is_empty() {
+        name.to_string()
+    } else {
+        format!("{name}@{version}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_distribution_cannot_have_two_alternatives() {
+        let package = |alternatives: &str| {
+            ron::from_str::<Package>(&format!(
+                "(name: \"Fish\", source: Apt(\"fish\"), page: Shells, alternatives: {{ {alternatives} }})"
+            ))
+        };
+
+        let fish = package("\"arch\": Pacman(\"fish\"), \"fedora\": Dnf(\"fish\")").unwrap();
+        assert_eq!(fish.alternatives.len(), 2);
+
+        let err =
+            package("\"arch\": Pacman(\"fish\"), \"arch\": Pacman(\"fish-git\")").unwrap_err();
+        assert!(err.to_string().contains("arch"), "{err}");
+    }
 }
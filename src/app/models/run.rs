@@ -0,0 +1,97 @@
+// SPDX-License-Identifier: GPL-3.0
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use uuid::Uuid;
+
+use crate::engine::plan::Plan;
+use crate::engine::{deploy::Deployment, Event, Report};
+
+use super::package::Package;
+
+/// Tracks the progress of an engine run for display.
+#[derive(Debug, Clone)]
+pub struct Run {
+    pub id: Uuid,
+    /// The approved plan being executed.
+    pub plan: Plan,
+    pub packages: Vec<Package>,
+    pub states: HashMap<Uuid, State>,
+    pub output: HashMap<Uuid, Vec<String>>,
+    /// Config files written so far.
+    pub deployed: Vec<Deployment>,
+    /// Config files put back as they were before Blueprint wrote them.
+    pub restored: Vec<PathBuf>,
+    /// Config files that could not be written, with the reason.
+    pub deploy_errors: Vec<(PathBuf, String)>,
+    pub report: Option<Report>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum State {
+    Pending,
+    Installing,
+    Removing,
+    Installed,
+    Removed,
+    Failed(String),
+}
+
+impl Run {
+    pub fn new(plan: Plan) -> Self {
+        let packages: Vec<Package> = plan.pending().map(|step| step.package.clone()).collect();
+        let states = packages.iter().map(|p| (p.id, State::Pending)).collect();
+
+        Self {
+            id: Uuid::new_v4(),
+            plan,
+            packages,
+            states,
+            output: HashMap::new(),
+            deployed: vec![],
+            restored: vec![],
+            deploy_errors: vec![],
+            report: None,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.report.is_some()
+    }
+
+    pub fn state(&self, id: &Uuid) -> &State {
+        self.states.get(id).unwrap_or(&State::Pending)
+    }
+
+    pub fn handle(&mut self, event: Event) {
+        match event {
+            Event::Started(_) => {}
+            Event::Installing(id) => {
+                self.states.insert(id, State::Installing);
+            }
+            Event::Removing(id) => {
+                self.states.insert(id, State::Removing);
+            }
+            Event::Output(id, line) => self.output.entry(id).or_default().push(line),
+            Event::Installed(id) => {
+                self.states.insert(id, State::Installed);
+            }
+            Event::Removed(id) => {
+                self.states.insert(id, State::Removed);
+            }
+            Event::Failed(id, error) => {
+                self.states.insert(id, State::Failed(error));
+            }
+            Event::Deployed(deployment) => self.deployed.push(deployment),
+            Event::Restored(target) => self.restored.push(target),
+            Event::DeployFailed(id, target, error) => {
+                if let Some(state) = self.states.get_mut(&id) {
+                    *state = State::Failed(error.clone());
+                }
+                self.deploy_errors.push((target, error));
+            }
+            Event::Finished(report) => self.report = Some(report),
+        }
+    }
+}
@@ -1,15 +1,21 @@
 // SPDX-License-Identifier: GPL-3.0
 
+use std::collections::{HashMap, HashSet};
+
 use cosmic::{
     cosmic_config::{self, CosmicConfigEntry},
     iced::Length,
     prelude::CollectionWidget,
-    widget::{self, icon},
+    widget::{self, icon, menu},
     Application, Apply, Element,
 };
 use uuid::Uuid;
 
-use crate::{config::Config, fl};
+use crate::{
+    config::Config,
+    engine::{detect::Status, host::HostInfo},
+    fl,
+};
 
 use super::{models::package::Package, AppModel, Page};
 
@@ -19,24 +25,51 @@ pub struct PageView {
     config_handler: Option<cosmic_config::Config>,
     title: String,
     packages: Vec<Package>,
+    statuses: HashMap<Uuid, Status>,
+    host: HostInfo,
+    /// Packages with config files that were changed on disk.
+    drifted: HashSet<Uuid>,
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
     ReloadPackages,
+    Statuses(HashMap<Uuid, Status>),
+    Host(HostInfo),
+    Drifted(HashSet<Uuid>),
+    ShowDrift(Uuid),
     EditPackage(Uuid),
+    TogglePackage(Uuid, bool),
+    DuplicatePackage(Uuid),
+    MovePackage(Uuid, Page),
+    DeletePackage(Uuid),
 }
 
 #[derive(Debug, Clone)]
 pub enum Command {
+    ShowDrift(Uuid),
     EditPackage(Uuid),
+    TogglePackage(Uuid, bool),
+    DuplicatePackage(Uuid),
+    MovePackage(Uuid, Page),
+    DeletePackage(Uuid),
+}
+
+/// Entries of the overflow menu of a package row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RowAction {
+    Edit(Uuid),
+    Duplicate(Uuid),
+    /// Moves the package to the page at this index of [`Page::ALL`].
+    Move(Uuid, usize),
+    Delete(Uuid),
 }
 
 impl PageView {
     pub fn new(page: Page, config: Config) -> Self {
         let title = page.to_string();
         let packages = config
-            .packages
+            .active_packages()
             .iter()
             .cloned()
             .filter(|p| p.page == page)
@@ -48,6 +81,9 @@ impl PageView {
             config_handler: cosmic_config::Config::new(AppModel::APP_ID, Config::VERSION).ok(),
             title,
             packages,
+            statuses: HashMap::new(),
+            host: HostInfo::default(),
+            drifted: HashSet::new(),
         }
     }
 
@@ -56,7 +92,15 @@ impl PageView {
         let packages: Vec<Element<'a, Message>> = self
             .packages
             .iter()
-            .map(|package| Self::package_row(package))
+            .map(|package| {
+                let available = self.host.supports(self.host.source_for(package));
+                Self::package_row(
+                    package,
+                    self.statuses.get(&package.id),
+                    available,
+                    self.drifted.contains(&package.id),
+                )
+            })
             .collect();
 
         if packages.is_empty() {
@@ -77,29 +121,118 @@ impl PageView {
             .into()
     }
 
-    pub fn package_row<'a>(package: &Package) -> Element<'a, Message> {
-        let spacing = cosmic::theme::active().cosmic().spacing;
+    /// A row for `package`, greyed out when it cannot be installed on this machine.
+    pub fn package_row<'a>(
+        package: &Package,
+        status: Option<&Status>,
+        available: bool,
+        drifted: bool,
+    ) -> Element<'a, Message> {
+        let theme = cosmic::theme::active();
+        let spacing = theme.cosmic().spacing;
+        let id = package.id;
+
+        let style = || {
+            if available {
+                cosmic::theme::Text::Default
+            } else {
+                cosmic::theme::Text::Color(theme.cosmic().palette.neutral_6.into())
+            }
+        };
 
         let description = if package.description.is_empty() {
             None
         } else {
-            Some(widget::text::caption(package.description.clone()))
+            Some(widget::text::caption(package.description.clone()).style(style()))
+        };
+
+        let badge = if available || matches!(status, Some(Status::Installed)) {
+            Self::status_badge(status)
+        } else {
+            Self::badge(fl!("unavailable"), "action-unavailable-symbolic")
         };
 
         widget::settings::item_row(vec![
             widget::column()
-                .push(widget::text(package.name.clone()))
+                .push(widget::text(package.name.clone()).style(style()))
                 .push_maybe(description)
                 .spacing(spacing.space_xxxs)
                 .into(),
             widget::horizontal_space(Length::Fill).into(),
-            widget::button(icon::from_name("view-more-symbolic"))
-                .on_press(Message::EditPackage(package.id.clone()))
-                .into(),
+            Self::drift_indicator(id, drifted),
+            badge,
+            widget::toggler(None, package.enabled, move |enabled| {
+                Message::TogglePackage(id, enabled)
+            })
+            .into(),
+            Self::row_menu(package),
         ])
         .into()
     }
 
+    /// The overflow menu with the actions available for `package`.
+    fn row_menu<'a>(package: &Package) -> Element<'a, Message> {
+        let id = package.id;
+        let pages = Page::ALL
+            .iter()
+            .enumerate()
+            .filter(|(_, page)| **page != package.page)
+            .map(|(index, page)| menu::Item::Button(page.to_string(), RowAction::Move(id, index)))
+            .collect();
+
+        let items = menu::items(
+            &HashMap::new(),
+            vec![
+                menu::Item::Button(fl!("edit-package"), RowAction::Edit(id)),
+                menu::Item::Button(fl!("duplicate-package"), RowAction::Duplicate(id)),
+                menu::Item::Folder(fl!("move-to"), pages),
+                menu::Item::Divider,
+                menu::Item::Button(fl!("delete-package"), RowAction::Delete(id)),
+            ],
+        );
+
+        menu::bar(vec![menu::Tree::with_children(
+            widget::button::icon(icon::from_name("view-more-symbolic")),
+            items,
+        )])
+        .into()
+    }
+
+    /// A button to review the config files of the package changed on disk.
+    fn drift_indicator<'a>(id: Uuid, drifted: bool) -> Element<'a, Message> {
+        if !drifted {
+            return widget::horizontal_space(Length::Shrink).into();
+        }
+
+        widget::button::text(fl!("drifted"))
+            .leading_icon(icon::from_name("dialog-warning-symbolic").size(16))
+            .on_press(Message::ShowDrift(id))
+            .into()
+    }
+
+    fn status_badge<'a>(status: Option<&Status>) -> Element<'a, Message> {
+        let (label, icon_name) = match status {
+            Some(Status::Installed) => (fl!("installed"), "emblem-ok-symbolic"),
+            Some(Status::Missing) => (fl!("missing"), "list-add-symbolic"),
+            Some(Status::Outdated { installed }) => (
+                fl!("outdated", installed = installed.clone()),
+                "software-update-available-symbolic",
+            ),
+            None => return widget::horizontal_space(Length::Shrink).into(),
+        };
+
+        Self::badge(label, icon_name)
+    }
+
+    fn badge<'a>(label: String, icon_name: &'static str) -> Element<'a, Message> {
+        widget::row()
+            .push(icon::from_name(icon_name).size(16).icon())
+            .push(widget::text::caption(label))
+            .spacing(cosmic::theme::active().cosmic().spacing.space_xxs)
+            .align_items(cosmic::iced::Alignment::Center)
+            .into()
+    }
+
     pub fn update(&mut self, message: Message) -> Vec<Command> {
         let mut commands = vec![];
         match message {
@@ -111,7 +244,7 @@ impl PageView {
                 }
                 let packages = self
                     .config
-                    .packages
+                    .active_packages()
                     .iter()
                     .cloned()
                     .filter(|p| p.page == self.page)
@@ -119,8 +252,33 @@ impl PageView {
 
                 self.packages = packages
             }
+            Message::Statuses(statuses) => self.statuses = statuses,
+            Message::Host(host) => self.host = host,
+            Message::Drifted(drifted) => self.drifted = drifted,
+            Message::ShowDrift(id) => commands.push(Command::ShowDrift(id)),
             Message::EditPackage(id) => commands.push(Command::EditPackage(id)),
+            Message::TogglePackage(id, enabled) => {
+                commands.push(Command::TogglePackage(id, enabled))
+            }
+            Message::DuplicatePackage(id) => commands.push(Command::DuplicatePackage(id)),
+            Message::MovePackage(id, page) => commands.push(Command::MovePackage(id, page)),
+            Message::DeletePackage(id) => commands.push(Command::DeletePackage(id)),
         }
         commands
     }
 }
+
+impl menu::action::MenuAction for RowAction {
+    type Message = Message;
+
+    fn message(&self) -> Self::Message {
+        match *self {
+            RowAction::Edit(id) => Message::EditPackage(id),
+            RowAction::Duplicate(id) => Message::DuplicatePackage(id),
+            RowAction::Move(id, index) => {
+                Message::MovePackage(id, Page::ALL.get(index).cloned().unwrap_or_default())
+            }
+            RowAction::Delete(id) => Message::DeletePackage(id),
+        }
+    }
+}
@@ -0,0 +1,752 @@
+// SPDX-License-Identifier: GPL-3.0
+
+//! Headless command-line interface working on the same blueprint as the GUI.
+
+use std::collections::HashSet;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand};
+use cosmic::cosmic_config;
+
+use crate::app::models::blueprint::Blueprint;
+use crate::config::Config;
+use crate::engine::detect::{Installed, Status};
+use crate::engine::helper::{self, Elevation};
+use crate::engine::host::HostInfo;
+use crate::engine::journal::{Journal, Record, Transaction};
+use crate::engine::plan::{Action, FileAction, Plan};
+use crate::engine::template::{self, Variables};
+use crate::engine::{Engine, Event};
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "blueprint",
+    version,
+    about = "An out of the box development environment designer"
+)]
+pub struct Cli {
+    /// Use this profile instead of the active one.
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+    /// Gives a value to a variable used by templated config files, overriding the
+    /// saved one. Can be repeated.
+    #[arg(long = "var", value_name = "NAME=VALUE", value_parser = parse_variable, global = true)]
+    pub variables: Vec<(String, String)>,
+    /// Runs the given command instead of opening the application window.
+    #[command(subcommand)]
+    pub command: Option<Commands>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Commands {
+    /// Installs the blueprint's packages on this machine.
+    Apply {
+        /// Apply without asking for confirmation.
+        #[arg(short, long)]
+        yes: bool,
+        /// Also remove disabled packages that are installed.
+        #[arg(long)]
+        remove: bool,
+    },
+    /// Shows what applying the blueprint would change.
+    Plan {
+        /// Read the package databases of the system mounted at this directory.
+        #[arg(long, default_value = "/")]
+        root: PathBuf,
+    },
+    /// Lists the blueprint's packages and whether they are installed.
+    List {
+        /// Read the package databases of the system mounted at this directory.
+        #[arg(long, default_value = "/")]
+        root: PathBuf,
+    },
+    /// Shows the detected distribution and package managers.
+    Host {
+        /// Inspect the system mounted at this directory.
+        #[arg(long, default_value = "/")]
+        root: PathBuf,
+    },
+    /// Lists the variables templated config files can use and their values.
+    Variables,
+    /// Lists the available profiles.
+    Profiles,
+    /// Writes the blueprint to a .ron or .toml file.
+    Export { path: PathBuf },
+    /// Adds the packages of a blueprint file to the blueprint.
+    Import {
+        path: PathBuf,
+        /// Replace the blueprint's packages instead of adding to them.
+        #[arg(long)]
+        replace: bool,
+    },
+    /// Checks a blueprint file, or the current blueprint, for packages that cannot be applied.
+    Validate { path: Option<PathBuf> },
+    /// Removes the packages Blueprint installed and puts back the config files it
+    /// replaced.
+    Revert {
+        /// Revert without asking for confirmation.
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Lists past runs and what each did, newest first.
+    History,
+    /// Undoes one past run, given by the start of its id as shown by `history`.
+    Rollback {
+        run: String,
+        /// Roll back without asking for confirmation.
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Runs package manager calls as root for a run; started through pkexec or sudo.
+    #[command(hide = true)]
+    Helper,
+    /// Lists the packages installed on this machine, or writes them to a .ron or .toml
+    /// blueprint file.
+    Scan { path: Option<PathBuf> },
+}
+
+/// Runs the command given on the command line to completion.
+pub fn run(cli: Cli) -> ExitCode {
+    // The helper runs as root and must not touch the user's configuration.
+    if let Some(Commands::Helper) = cli.command {
+        return helper::serve();
+    }
+
+    let result = load(cli.profile.as_deref()).and_then(|(handler, mut config)| {
+        let Some(command) = cli.command else {
+            return Ok(ExitCode::SUCCESS);
+        };
+
+        let variables = |host: &HostInfo| {
+            let mut variables = config.variables(host);
+            variables.extend(cli.variables.iter().cloned());
+            variables
+        };
+
+        match command {
+            Commands::Apply { yes, remove } => {
+                let variables = variables(&HostInfo::detect(Path::new("/")));
+                apply(handler, &mut config, yes, remove, variables)
+            }
+            Commands::Revert { yes } => revert(handler, &mut config, yes),
+            Commands::History => history(),
+            Commands::Rollback { run, yes } => rollback(handler, &mut config, &run, yes),
+            Commands::Plan { root } => {
+                let host = HostInfo::detect(&root);
+                print_plan(&Plan::new(
+                    config.active_packages(),
+                    &Installed::load(&root),
+                    &host,
+                    &variables(&host),
+                ));
+                Ok(ExitCode::SUCCESS)
+            }
+            Commands::List { root } => {
+                list(&config, &root);
+                Ok(ExitCode::SUCCESS)
+            }
+            Commands::Host { root } => {
+                let host = HostInfo::detect(&root);
+                let name = if host.os.pretty_name.is_empty() {
+                    &host.os.id
+                } else {
+                    &host.os.pretty_name
+                };
+                println!("Distribution:     {name}");
+                println!(
+                    "Identifiers:      {}",
+                    host.os.ids().collect::<Vec<_>>().join(" ")
+                );
+                let managers = host.managers.iter().map(ToString::to_string);
+                println!(
+                    "Package managers: {}",
+                    managers.collect::<Vec<_>>().join(", ")
+                );
+                Ok(ExitCode::SUCCESS)
+            }
+            Commands::Variables => {
+                let values = variables(&HostInfo::detect(Path::new("/")));
+                let declared = config
+                    .active()
+                    .map(|profile| profile.variables.as_slice())
+                    .unwrap_or_default();
+
+                let builtins = template::BUILTINS.iter().map(|name| (*name, ""));
+                let declared = declared
+                    .iter()
+                    .map(|variable| (variable.name.as_str(), variable.description.as_str()));
+                for (name, description) in builtins.chain(declared) {
+                    let value = values.get(name).map_or("(no value)", String::as_str);
+                    println!("{name:<16} {value:<32} {description}");
+                }
+                Ok(ExitCode::SUCCESS)
+            }
+            Commands::Profiles => {
+                let active = config.active().map(|profile| profile.id);
+                for profile in &config.profiles {
+                    let marker = if Some(profile.id) == active { "*" } else { " " };
+                    println!("{marker} {:<24} {}", profile.name, profile.description);
+                }
+                Ok(ExitCode::SUCCESS)
+            }
+            Commands::Export { path } => export(&config, &path),
+            Commands::Import { path, replace } => import(handler, config, &path, replace),
+            Commands::Validate { path } => validate(&config, path.as_deref()),
+            Commands::Scan { path } => scan(path.as_deref()),
+            Commands::Helper => unreachable!("the helper is started before loading"),
+        }
+    });
+
+    result.unwrap_or_else(|err| {
+        eprintln!("error: {err}");
+        ExitCode::FAILURE
+    })
+}
+
+/// Loads the configuration with the requested profile made active.
+fn load(profile: Option<&str>) -> Result<(Option<cosmic_config::Config>, Config), String> {
+    let (handler, mut config) = Config::load();
+
+    if let Some(profile) = profile {
+        if !config.select(profile) {
+            return Err(format!("no profile named \"{profile}\""));
+        }
+    }
+
+    Ok((handler, config))
+}
+
+fn apply(
+    handler: Option<cosmic_config::Config>,
+    config: &mut Config,
+    yes: bool,
+    remove: bool,
+    variables: Variables,
+) -> Result<ExitCode, String> {
+    let root = Path::new("/");
+    let host = HostInfo::detect(root);
+
+    let missing = template::missing(config.active_packages(), &variables);
+    if !missing.is_empty() {
+        return Err(format!(
+            "no value for {}, give one with --var NAME=VALUE",
+            missing.join(", ")
+        ));
+    }
+
+    let mut plan = Plan::new(
+        config.active_packages(),
+        &Installed::load(root),
+        &host,
+        &variables,
+    );
+
+    for step in &mut plan.packages {
+        match step.action {
+            Action::Remove => step.approved = remove,
+            Action::Bootstrap => {
+                step.approved =
+                    yes || confirm("rustup is missing, install it with https://sh.rustup.rs?")?
+            }
+            _ => {}
+        }
+    }
+
+    print_plan(&plan);
+
+    if plan.is_empty() {
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if !yes && !confirm("Proceed?")? {
+        return Ok(ExitCode::FAILURE);
+    }
+
+    execute(handler, config, plan)
+}
+
+fn revert(
+    handler: Option<cosmic_config::Config>,
+    config: &mut Config,
+    yes: bool,
+) -> Result<ExitCode, String> {
+    if config.applied.is_empty() {
+        println!("Blueprint has not installed or written anything on this machine.");
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let plan = Plan::revert(&config.applied, &Installed::load(Path::new("/")));
+    print_plan(&plan);
+
+    if plan.is_empty() {
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if !yes && !confirm("Revert these changes?")? {
+        return Ok(ExitCode::FAILURE);
+    }
+
+    execute(handler, config, plan)
+}
+
+fn history() -> Result<ExitCode, String> {
+    for transaction in load_history()? {
+        let interrupted = if transaction.is_finished() {
+            ""
+        } else {
+            " (interrupted)"
+        };
+        println!(
+            "{}  {}{interrupted}",
+            &transaction.id.to_string()[..8],
+            crate::engine::deploy::date_time(transaction.started_at())
+        );
+
+        for record in &transaction.records {
+            match record {
+                Record::Started | Record::Finished => {}
+                Record::Command {
+                    command, status, ..
+                } => match status {
+                    Some(code) => println!("    $ {command} (exit {code})"),
+                    None => println!("    $ {command} (did not exit)"),
+                },
+                Record::Installed { name, source, .. } => {
+                    println!("    installed {name} ({source})")
+                }
+                Record::Removed { name, source, .. } => println!("    removed {name} ({source})"),
+                Record::Failed { error, .. } => println!("    failed: {error}"),
+                Record::Written {
+                    target,
+                    backup: Some(backup),
+                    ..
+                } => println!(
+                    "    wrote {} (backup at {})",
+                    target.display(),
+                    backup.display()
+                ),
+                Record::Written { target, .. } => println!("    wrote {}", target.display()),
+                Record::Restored { target } => println!("    restored {}", target.display()),
+            }
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn rollback(
+    handler: Option<cosmic_config::Config>,
+    config: &mut Config,
+    run: &str,
+    yes: bool,
+) -> Result<ExitCode, String> {
+    let matching: Vec<_> = load_history()?
+        .into_iter()
+        .filter(|transaction| transaction.id.to_string().starts_with(run))
+        .collect();
+    let transaction = match matching.as_slice() {
+        [transaction] => transaction,
+        [] => return Err(format!("no run with an id starting with \"{run}\"")),
+        _ => {
+            return Err(format!(
+                "more than one run has an id starting with \"{run}\""
+            ))
+        }
+    };
+
+    let plan = Plan::revert(&transaction.applied(), &Installed::load(Path::new("/")));
+    print_plan(&plan);
+
+    if plan.is_empty() {
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if !yes && !confirm("Roll this run back?")? {
+        return Ok(ExitCode::FAILURE);
+    }
+
+    execute(handler, config, plan)
+}
+
+fn load_history() -> Result<Vec<Transaction>, String> {
+    let journal = Journal::open().ok_or("there is no state directory for the journal")?;
+    journal.load().map_err(|err| err.to_string())
+}
+
+/// Runs the approved steps of `plan`, printing progress, and records what changed.
+fn execute(
+    handler: Option<cosmic_config::Config>,
+    config: &mut Config,
+    plan: Plan,
+) -> Result<ExitCode, String> {
+    let packages: Vec<_> = plan.pending().map(|step| step.package.clone()).collect();
+    let names = plan
+        .packages
+        .iter()
+        .map(|step| (step.package.id, step.package.name.clone()))
+        .collect::<std::collections::HashMap<_, _>>();
+    let name = |id: uuid::Uuid| names.get(&id).map(String::as_str).unwrap_or_default();
+
+    let runtime = tokio::runtime::Runtime::new().map_err(|err| err.to_string())?;
+    let report = runtime.block_on(async {
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let engine = tokio::spawn(Engine::new(plan).elevate_with(Elevation::Sudo).run(sender));
+
+        while let Some(event) = receiver.recv().await {
+            match event {
+                Event::Started(total) => println!("Applying {total} package(s)"),
+                Event::Installing(id) => println!("==> Installing {}", name(id)),
+                Event::Removing(id) => println!("==> Removing {}", name(id)),
+                Event::Output(_, line) => println!("    {line}"),
+                Event::Installed(id) => println!("==> Installed {}", name(id)),
+                Event::Removed(id) => println!("==> Removed {}", name(id)),
+                Event::Failed(id, error) => eprintln!("==> Failed {}: {error}", name(id)),
+                Event::Deployed(deployment) => match deployment.backup {
+                    Some(backup) => println!(
+                        "==> Wrote {} (backup at {})",
+                        deployment.target.display(),
+                        backup.display()
+                    ),
+                    None => println!("==> Wrote {}", deployment.target.display()),
+                },
+                Event::Restored(target) => println!("==> Restored {}", target.display()),
+                Event::DeployFailed(_, target, error) => {
+                    eprintln!("==> Failed to write {}: {error}", target.display())
+                }
+                Event::Finished(_) => {}
+            }
+        }
+
+        engine.await.map_err(|err| err.to_string())
+    })?;
+
+    println!(
+        "{} installed, {} removed, {} files written, {} restored, {} failed",
+        report.installed.len(),
+        report.removed.len(),
+        report.deployed.len(),
+        report.restored.len(),
+        report.failed.len()
+    );
+
+    match &handler {
+        Some(handler) => {
+            if let Err(err) = config.record(handler, &packages, &report) {
+                eprintln!("warning: failed to record the changes for reverting: {err}");
+            }
+        }
+        None => eprintln!("warning: the changes cannot be recorded for reverting"),
+    }
+
+    Ok(if report.is_success() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+fn print_plan(plan: &Plan) {
+    for step in &plan.packages {
+        let action = match (step.action, step.approved) {
+            (Action::Install, true) => "install",
+            (Action::Remove, true) => "remove",
+            (Action::Bootstrap, true) => "install (with https://sh.rustup.rs)",
+            (Action::Skip, _) => "skip (installed)",
+            (Action::Unavailable, _) => "skip (no package manager for it)",
+            (Action::Invalid, _) => "skip (invalid, see `blueprint validate`)",
+            (_, false) => "skip (not approved)",
+        };
+        println!(
+            "{:<24} {:<32} {action}",
+            step.package.name, step.package.source
+        );
+    }
+
+    let pending: Vec<&Path> = plan
+        .pending_files()
+        .map(|file| file.target.as_path())
+        .collect();
+    for file in &plan.files {
+        let action = match file.action {
+            FileAction::Unchanged => "unchanged",
+            FileAction::Invalid => "skip (cannot be written)",
+            _ if !pending.contains(&file.target.as_path()) => "skip (package not applied)",
+            FileAction::Create => "create",
+            FileAction::Overwrite => "overwrite",
+            FileAction::Restore if file.backup.is_some() => "restore",
+            FileAction::Restore => "delete",
+        };
+        println!("{:<57} {action}", file.target.display());
+        match file.action {
+            FileAction::Unchanged => {}
+            FileAction::Invalid => println!("    {}", file.diff),
+            _ => print!("{}", file.diff),
+        }
+    }
+
+    if plan.is_empty() {
+        println!("Nothing to do.");
+    }
+}
+
+fn list(config: &Config, root: &Path) {
+    let installed = Installed::load(root);
+    let host = HostInfo::detect(root);
+
+    for package in config.active_packages() {
+        let source = host.source_for(package);
+        let status = match installed.status(source) {
+            Status::Installed => "installed".to_string(),
+            Status::Missing if !host.supports(source) => "unavailable".to_string(),
+            Status::Missing => "missing".to_string(),
+            Status::Outdated { installed } => format!("outdated ({installed})"),
+        };
+        let enabled = if package.enabled { "" } else { " [disabled]" };
+        println!(
+            "{:<10} {:<24} {:<32} {status}{enabled}",
+            package.page.to_string(),
+            package.name,
+            source
+        );
+    }
+}
+
+fn export(config: &Config, path: &Path) -> Result<ExitCode, String> {
+    let blueprint = config.active().ok_or("there is no profile to export")?;
+    blueprint.save(path).map_err(|err| err.to_string())?;
+    println!(
+        "Exported {} package(s) to {}",
+        blueprint.packages.len(),
+        path.display()
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+fn import(
+    handler: Option<cosmic_config::Config>,
+    mut config: Config,
+    path: &Path,
+    replace: bool,
+) -> Result<ExitCode, String> {
+    let blueprint = Blueprint::load(path).map_err(|err| err.to_string())?;
+    let imported = blueprint.packages;
+    let handler = handler.ok_or("the configuration could not be opened")?;
+
+    let mut packages = if replace {
+        vec![]
+    } else {
+        config.active_packages().to_vec()
+    };
+    let (count, existing) = (imported.len(), packages.len());
+    for package in imported {
+        if !packages.iter().any(|p| p.id == package.id) {
+            packages.push(package);
+        }
+    }
+    let added = packages.len() - existing;
+
+    config
+        .update_active(&handler, |profile| {
+            profile.packages = packages;
+            for variable in blueprint.variables {
+                if !profile.variables.iter().any(|v| v.name == variable.name) {
+                    profile.variables.push(variable);
+                }
+            }
+        })
+        .map_err(|err| err.to_string())?;
+    println!(
+        "Imported {added} of {count} package(s) from {}",
+        path.display()
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+fn validate(config: &Config, path: Option<&Path>) -> Result<ExitCode, String> {
+    let packages = match path {
+        Some(path) => {
+            Blueprint::load(path)
+                .map_err(|err| err.to_string())?
+                .packages
+        }
+        None => config.active_packages().to_vec(),
+    };
+
+    let mut valid = true;
+    let mut ids = HashSet::new();
+    for package in &packages {
+        if !ids.insert(package.id) {
+            valid = false;
+            println!("{}: duplicate id {}", package.name, package.id);
+        }
+        for error in package.validate() {
+            valid = false;
+            println!("{}: {error}", package.name);
+        }
+    }
+
+    if valid {
+        println!("{} package(s) are valid", packages.len());
+        Ok(ExitCode::SUCCESS)
+    } else {
+        Ok(ExitCode::FAILURE)
+    }
+}
+
+/// Parses a `NAME=VALUE` pair given with `--var`. An empty value would count as
+/// given, so it is refused rather than written into templates.
+fn parse_variable(variable: &str) -> Result<(String, String), String> {
+    match variable.split_once('=') {
+        Some((name, value)) if !name.is_empty() && !value.is_empty() => {
+            Ok((name.to_string(), value.to_string()))
+        }
+        Some((name, _)) if !name.is_empty() => Err(format!("no value given for {name}")),
+        _ => Err(format!("expected NAME=VALUE, got \"{variable}\"")),
+    }
+}
+
+fn scan(path: Option<&Path>) -> Result<ExitCode, String> {
+    let host = HostInfo::detect(Path::new("/"));
+    let packages = crate::engine::scan::scan(&host);
+
+    match path {
+        Some(path) => {
+            let name = match host.os.pretty_name.as_str() {
+                "" => "This system",
+                name => name,
+            };
+            let count = packages.len();
+            Blueprint::new(name, packages)
+                .save(path)
+                .map_err(|err| err.to_string())?;
+            println!("Wrote {count} package(s) to {}", path.display());
+        }
+        None => {
+            for package in &packages {
+                println!(
+                    "{:<10} {:<32} {}",
+                    package.page.to_string(),
+                    package.name,
+                    package.source
+                );
+            }
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn confirm(question: &str) -> Result<bool, String> {
+    print!("{question} [y/N] ");
+    std::io::stdout().flush().map_err(|err| err.to_string())?;
+
+    let mut answer = String::new();
+    std::io::stdin()
+        .lock()
+        .read_line(&mut answer)
+        .map_err(|err| err.to_string())?;
+
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::try_parse_from(std::iter::once("blueprint").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn variables_are_split_at_the_first_equals_sign() {
+        assert_eq!(
+            parse_variable("email=ada@example.com"),
+            Ok(("email".into(), "ada@example.com".into()))
+        );
+        assert_eq!(parse_variable("a=b=c"), Ok(("a".into(), "b=c".into())));
+        assert_eq!(
+            parse_variable("email="),
+            Err("no value given for email".into())
+        );
+        assert!(parse_variable("email").is_err());
+        assert!(parse_variable("=ada").is_err());
+        assert!(parse_variable("").is_err());
+    }
+
+    #[test]
+    fn no_command_opens_the_window() {
+        let cli = parse(&[]).unwrap();
+        assert!(cli.command.is_none());
+        assert!(cli.profile.is_none());
+        assert!(cli.variables.is_empty());
+    }
+
+    #[test]
+    fn commands_and_their_flags() {
+        let cli = parse(&[
+            "apply",
+            "-y",
+            "--remove",
+            "--var",
+            "name=Ada",
+            "--profile",
+            "work",
+        ])
+        .unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Apply {
+                yes: true,
+                remove: true
+            })
+        ));
+        assert_eq!(cli.profile.as_deref(), Some("work"));
+        assert_eq!(cli.variables, [("name".into(), "Ada".into())]);
+
+        assert!(matches!(
+            parse(&["apply"]).unwrap().command,
+            Some(Commands::Apply {
+                yes: false,
+                remove: false
+            })
+        ));
+        assert!(matches!(
+            parse(&["plan", "--root", "/mnt"]).unwrap().command,
+            Some(Commands::Plan { root }) if root == Path::new("/mnt")
+        ));
+        assert!(matches!(
+            parse(&["list"]).unwrap().command,
+            Some(Commands::List { root }) if root == Path::new("/")
+        ));
+        assert!(matches!(
+            parse(&["import", "shells.ron", "--replace"]).unwrap().command,
+            Some(Commands::Import { path, replace: true }) if path == Path::new("shells.ron")
+        ));
+        assert!(matches!(
+            parse(&["validate"]).unwrap().command,
+            Some(Commands::Validate { path: None })
+        ));
+        assert!(matches!(
+            parse(&["rollback", "0a1b", "--yes"]).unwrap().command,
+            Some(Commands::Rollback { run, yes: true }) if run == "0a1b"
+        ));
+        assert!(matches!(
+            parse(&["revert"]).unwrap().command,
+            Some(Commands::Revert { yes: false })
+        ));
+        assert!(matches!(
+            parse(&["helper"]).unwrap().command,
+            Some(Commands::Helper)
+        ));
+    }
+
+    #[test]
+    fn bad_arguments_are_refused() {
+        assert!(parse(&["export"]).is_err());
+        assert!(parse(&["apply", "--var", "name"]).is_err());
+        assert!(parse(&["apply", "--force"]).is_err());
+        assert!(parse(&["install"]).is_err());
+    }
+}
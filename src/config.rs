@@ -1,11 +1,179 @@
 // SPDX-License-Identifier: GPL-3.0
 
+use std::collections::BTreeMap;
+
 use cosmic::cosmic_config::{self, cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use uuid::Uuid;
 
+use crate::app::models::blueprint::Blueprint;
 use crate::app::models::package::Package;
+use crate::engine::host::HostInfo;
+use crate::engine::revert::Applied;
+use crate::engine::template::{self, Variables};
+use crate::engine::Report;
+use crate::{engine, fl};
+
+pub const APP_ID: &str = "dev.edfloreshz.Blueprint";
 
 #[derive(Debug, Default, Clone, CosmicConfigEntry, Eq, PartialEq)]
 #[version = 1]
 pub struct Config {
+    /// Packages saved before profiles existed, moved into a profile on load.
     pub packages: Vec<Package>,
+    /// Named blueprints, such as one per team role.
+    pub profiles: Vec<Blueprint>,
+    pub active_profile: Uuid,
+    /// Values given to blueprint variables on this machine. They are kept out of
+    /// the blueprints so each user fills in their own.
+    pub variables: BTreeMap<String, String>,
+    /// What Blueprint installed and wrote on this machine, to revert it.
+    pub applied: Applied,
+}
+
+impl Config {
+    /// Opens the application's configuration and reads it, logging any errors.
+    pub fn load() -> (Option<cosmic_config::Config>, Self) {
+        let handler = cosmic_config::Config::new(APP_ID, Self::VERSION).ok();
+        let mut config = handler
+            .as_ref()
+            .map(|context| match Self::get_entry(context) {
+                Ok(config) => config,
+                Err((errors, config)) => {
+                    for error in errors {
+                        log::error!("error loading app config: {}", error);
+                    }
+
+                    config
+                }
+            })
+            .unwrap_or_default();
+
+        if let Some(handler) = &handler {
+            if let Err(err) = config.migrate(handler) {
+                log::error!("failed to migrate app config: {}", err);
+            }
+
+            if let Err(err) = config.pull_linked(handler) {
+                log::error!("failed to save edits made to linked config files: {}", err);
+            }
+        }
+
+        (handler, config)
+    }
+
+    /// Makes sure there is at least one profile, moving any packages saved before
+    /// profiles existed into it.
+    fn migrate(&mut self, handler: &cosmic_config::Config) -> Result<(), cosmic_config::Error> {
+        if !self.profiles.is_empty() {
+            return Ok(());
+        }
+
+        let profile = Blueprint::new(&fl!("default-profile"), self.packages.clone());
+        self.set_active_profile(handler, profile.id)?;
+        self.set_profiles(handler, vec![profile])?;
+        self.set_packages(handler, vec![])?;
+        Ok(())
+    }
+
+    /// Copies edits made through linked config files back into every profile.
+    pub fn pull_linked(
+        &mut self,
+        handler: &cosmic_config::Config,
+    ) -> Result<bool, cosmic_config::Error> {
+        let mut profiles = self.profiles.clone();
+        let mut changed = false;
+        for package in profiles
+            .iter_mut()
+            .flat_map(|profile| &mut profile.packages)
+        {
+            changed |= engine::deploy::pull_linked(package);
+        }
+
+        if changed {
+            self.set_profiles(handler, profiles)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// The profile being edited, falling back to the first one.
+    pub fn active(&self) -> Option<&Blueprint> {
+        self.active_index().map(|index| &self.profiles[index])
+    }
+
+    pub fn active_index(&self) -> Option<usize> {
+        self.profiles
+            .iter()
+            .position(|profile| profile.id == self.active_profile)
+            .or_else(|| (!self.profiles.is_empty()).then_some(0))
+    }
+
+    /// The packages of the active profile.
+    pub fn active_packages(&self) -> &[Package] {
+        self.active()
+            .map(|profile| profile.packages.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// The value of every variable the active profile's templates can use on `host`.
+    pub fn variables(&self, host: &HostInfo) -> Variables {
+        let declared = self
+            .active()
+            .map(|profile| profile.variables.as_slice())
+            .unwrap_or_default();
+        template::resolve(host, declared, &self.variables)
+    }
+
+    /// Makes the profile called `name` active without saving the change.
+    pub fn select(&mut self, name: &str) -> bool {
+        match self.profiles.iter().find(|profile| profile.name == name) {
+            Some(profile) => {
+                self.active_profile = profile.id;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Changes the active profile with `update` and saves the result.
+    pub fn update_active(
+        &mut self,
+        handler: &cosmic_config::Config,
+        update: impl FnOnce(&mut Blueprint),
+    ) -> Result<bool, cosmic_config::Error> {
+        let mut profiles = self.profiles.clone();
+        match self.active_index() {
+            Some(index) => update(&mut profiles[index]),
+            None => {
+                let mut profile = Blueprint::new(&fl!("default-profile"), vec![]);
+                update(&mut profile);
+                self.set_active_profile(handler, profile.id)?;
+                profiles.push(profile);
+            }
+        }
+
+        self.set_profiles(handler, profiles)
+    }
+
+    /// Replaces the packages of the active profile and saves them.
+    pub fn set_active_packages(
+        &mut self,
+        handler: &cosmic_config::Config,
+        packages: Vec<Package>,
+    ) -> Result<bool, cosmic_config::Error> {
+        self.update_active(handler, |profile| profile.packages = packages)
+    }
+
+    /// Records what a run of `packages` changed on this machine and saves it, so it
+    /// can be reverted.
+    pub fn record(
+        &mut self,
+        handler: &cosmic_config::Config,
+        packages: &[Package],
+        report: &Report,
+    ) -> Result<bool, cosmic_config::Error> {
+        let mut applied = self.applied.clone();
+        applied.record(packages, report);
+        self.set_applied(handler, applied)
+    }
 }
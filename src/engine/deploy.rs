@@ -0,0 +1,474 @@
+// SPDX-License-Identifier: GPL-3.0
+
+//! Writes the config files of a blueprint's packages to disk, either as copies or
+//! as symlinks to a managed copy kept by Blueprint.
+
+use std::fs::{self, Permissions};
+use std::io::{self, Write};
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::app::models::package::{ConfigFile, DeployMethod, Package};
+use crate::config::APP_ID;
+
+use super::merge::{self, Format};
+use super::template::{self, Variables};
+
+/// A config file written by the engine.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Deployment {
+    /// The package that owns the file.
+    pub package: Uuid,
+    pub target: PathBuf,
+    /// Where the previous contents of `target` were saved, if it existed.
+    pub backup: Option<PathBuf>,
+}
+
+/// What putting a config file in place did to its target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The target already was as it should be and was left alone.
+    Unchanged,
+    /// The target was written, after saving what it had to the backup, if it existed.
+    Written(Option<PathBuf>),
+}
+
+/// The state of the symlink of a linked config file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkStatus {
+    /// The target links to the managed copy.
+    Linked,
+    /// Nothing exists at the target yet.
+    Missing,
+    /// The target is a symlink to a missing file or to somewhere else.
+    Broken,
+    /// The target was replaced by a regular file.
+    Replaced,
+}
+
+/// What `file` should contain given the `current` contents of its target: its
+/// content with placeholders filled in with `variables`, merged into `current` if the
+/// file is merged.
+pub fn expected(
+    file: &ConfigFile,
+    current: Option<&str>,
+    variables: &Variables,
+) -> Result<String, String> {
+    let content = if file.template {
+        template::render(&file.content, variables).map_err(|err| err.to_string())?
+    } else {
+        file.content.clone()
+    };
+
+    if file.method != DeployMethod::Merge {
+        return Ok(content);
+    }
+
+    let format = Format::from_extension(&file.extension)
+        .ok_or_else(|| format!("files with extension `{}` cannot be merged", file.extension))?;
+    merge::merge(current.unwrap_or_default(), &content, format).map_err(|err| err.to_string())
+}
+
+/// Writes `content` to `target`, creating missing parent directories and backing up
+/// whatever was there before.
+///
+/// The file gets `mode` if one is given and keeps the permissions of the file it
+/// replaces otherwise. A file that already has `content` and `mode` is left alone.
+pub fn deploy(target: &Path, content: &str, mode: Option<u32>) -> io::Result<Outcome> {
+    create_parent(target)?;
+
+    let (backup, permissions) = match fs::metadata(target) {
+        Ok(metadata) if has_mode(&metadata, mode) && fs::read(target)? == content.as_bytes() => {
+            return Ok(Outcome::Unchanged);
+        }
+        Ok(metadata) => {
+            let backup = backup_path(target, SystemTime::now());
+            fs::copy(target, &backup)?;
+            (Some(backup), Some(metadata.permissions()))
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => (None, None),
+        Err(err) => return Err(err),
+    };
+
+    write(
+        target,
+        content,
+        mode.map(Permissions::from_mode).or(permissions),
+    )?;
+    Ok(Outcome::Written(backup))
+}
+
+/// Writes `content` to the `managed` copy and symlinks `target` to it, moving
+/// whatever was at `target` before to a backup.
+///
+/// A target already linked keeps its link. Only its managed copy is written, after a
+/// backup of what the link showed, unless it already has `content` and `mode`.
+pub fn link(
+    target: &Path,
+    managed: &Path,
+    content: &str,
+    mode: Option<u32>,
+) -> io::Result<Outcome> {
+    create_parent(managed)?;
+
+    let backup = match link_status(target, managed) {
+        LinkStatus::Linked => {
+            let metadata = fs::metadata(managed)?;
+            if has_mode(&metadata, mode) && fs::read(managed)? == content.as_bytes() {
+                return Ok(Outcome::Unchanged);
+            }
+
+            let backup = backup_path(target, SystemTime::now());
+            fs::copy(managed, &backup)?;
+            write(
+                managed,
+                content,
+                mode.map(Permissions::from_mode)
+                    .or(Some(metadata.permissions())),
+            )?;
+            return Ok(Outcome::Written(Some(backup)));
+        }
+        LinkStatus::Missing => None,
+        LinkStatus::Broken | LinkStatus::Replaced => {
+            let backup = backup_path(target, SystemTime::now());
+            fs::rename(target, &backup)?;
+            Some(backup)
+        }
+    };
+
+    write(managed, content, mode.map(Permissions::from_mode))?;
+    create_parent(target)?;
+    std::os::unix::fs::symlink(managed, target)?;
+    Ok(Outcome::Written(backup))
+}
+
+/// Puts the `backup` of `target` back in its place, or removes `target` if it did not
+/// exist before Blueprint wrote it. The `managed` copy of a linked file is removed
+/// along with its link.
+pub fn restore(target: &Path, backup: Option<&Path>, managed: Option<&Path>) -> io::Result<()> {
+    let ignore_missing = |result: io::Result<()>| match result {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    };
+
+    // Renaming replaces a link or file at `target` in one step.
+    match backup {
+        Some(backup) => {
+            create_parent(target)?;
+            fs::rename(backup, target)?;
+        }
+        None => ignore_missing(fs::remove_file(target))?,
+    }
+
+    match managed {
+        Some(managed) => ignore_missing(fs::remove_file(managed)),
+        None => Ok(()),
+    }
+}
+
+/// Where the managed copy of the linked config file of `package` at `target` is
+/// kept, mirroring its path below the home directory.
+pub fn managed_path(package: Uuid, target: &Path) -> Option<PathBuf> {
+    let relative = dirs::home_dir()
+        .and_then(|home| target.strip_prefix(home).ok())
+        .unwrap_or_else(|| target.strip_prefix("/").unwrap_or(target));
+
+    Some(
+        dirs::data_dir()?
+            .join(APP_ID)
+            .join("files")
+            .join(package.to_string())
+            .join(relative),
+    )
+}
+
+pub fn link_status(target: &Path, managed: &Path) -> LinkStatus {
+    let Ok(metadata) = fs::symlink_metadata(target) else {
+        return LinkStatus::Missing;
+    };
+
+    if !metadata.is_symlink() {
+        return LinkStatus::Replaced;
+    }
+
+    match fs::read_link(target) {
+        Ok(destination) if destination == managed && managed.exists() => LinkStatus::Linked,
+        _ => LinkStatus::Broken,
+    }
+}
+
+/// Copies edits made through the symlinks of `package` back into its config files.
+///
+/// Templates are skipped, since their managed copy has the placeholders filled in.
+/// Returns whether any content changed.
+pub fn pull_linked(package: &mut Package) -> bool {
+    let mut changed = false;
+
+    for file in &mut package.config {
+        if file.method != DeployMethod::Symlink || file.template {
+            continue;
+        }
+
+        let target = file.target_path();
+        let Some(managed) = managed_path(package.id, &target) else {
+            continue;
+        };
+
+        if link_status(&target, &managed) != LinkStatus::Linked {
+            continue;
+        }
+
+        match fs::read_to_string(&managed) {
+            Ok(content) if content != file.content => {
+                file.content = content;
+                changed = true;
+            }
+            Ok(_) => {}
+            Err(err) => log::error!("failed to read {}: {}", managed.display(), err),
+        }
+    }
+
+    changed
+}
+
+/// Writes the config files of `package` that are linked into place to their managed
+/// copies, so the links show edits made in Blueprint right away.
+///
+/// Templates are left for the next apply to render.
+pub fn push_linked(package: &Package) -> io::Result<()> {
+    for file in &package.config {
+        if file.method != DeployMethod::Symlink || file.template {
+            continue;
+        }
+
+        let target = file.target_path();
+        let Some(managed) = managed_path(package.id, &target) else {
+            continue;
+        };
+
+        if link_status(&target, &managed) == LinkStatus::Linked {
+            write(
+                &managed,
+                &file.content,
+                file.mode.map(Permissions::from_mode),
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether a file with `metadata` has the permissions `mode`, if one is given.
+pub fn has_mode(metadata: &fs::Metadata, mode: Option<u32>) -> bool {
+    match mode {
+        Some(mode) => metadata.permissions().mode() & 0o7777 == mode,
+        None => true,
+    }
+}
+
+fn create_parent(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(parent) => fs::create_dir_all(parent),
+        None => Ok(()),
+    }
+}
+
+/// Writes next to `path` and renames, so the file is never left half written.
+///
+/// The temporary file has a name no one can guess beforehand and is created with
+/// `permissions` already set, so its contents are never readable by others, even
+/// for a moment.
+fn write(path: &Path, content: &str, permissions: Option<Permissions>) -> io::Result<()> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "target has no file name"))?;
+    let temporary = path.with_file_name(format!(
+        ".{}.{}.blueprint",
+        name.to_string_lossy(),
+        Uuid::new_v4().simple()
+    ));
+
+    let result =
+        write_new(&temporary, content, permissions).and_then(|()| fs::rename(&temporary, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    result
+}
+
+/// Creates `path`, failing if it exists, and writes `content` to it.
+fn write_new(path: &Path, content: &str, permissions: Option<Permissions>) -> io::Result<()> {
+    let mode = permissions
+        .as_ref()
+        .map(|permissions| permissions.mode() & 0o7777)
+        .unwrap_or(0o666);
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(mode)
+        .open(path)?;
+
+    // The umask may have taken bits off the mode the file was created with.
+    if let Some(permissions) = permissions {
+        file.set_permissions(permissions)?;
+    }
+
+    file.write_all(content.as_bytes())?;
+    file.sync_all()
+}
+
+/// A path next to `target` that does not exist yet, named after `time`, such as
+/// `config.fish.20241018T093000.bak`.
+pub fn backup_path(target: &Path, time: SystemTime) -> PathBuf {
+    let name = target
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let stamp = timestamp(time);
+
+    let mut backup = target.with_file_name(format!("{name}.{stamp}.bak"));
+    let mut attempt = 1;
+    while backup.exists() {
+        backup = target.with_file_name(format!("{name}.{stamp}-{attempt}.bak"));
+        attempt += 1;
+    }
+    backup
+}
+
+/// Formats `time` in UTC as `YYYYMMDDTHHMMSS`.
+pub fn timestamp(time: SystemTime) -> String {
+    let (year, month, day, hours, minutes, seconds) = civil(time);
+    format!("{year:04}{month:02}{day:02}T{hours:02}{minutes:02}{seconds:02}")
+}
+
+/// Formats `time` in UTC for display, as `YYYY-MM-DD HH:MM:SS UTC`.
+pub fn date_time(time: SystemTime) -> String {
+    let (year, month, day, hours, minutes, seconds) = civil(time);
+    format!("{year:04}-{month:02}-{day:02} {hours:02}:{minutes:02}:{seconds:02} UTC")
+}
+
+/// Splits `time` into the UTC year, month, day, hours, minutes and seconds.
+fn civil(time: SystemTime) -> (i64, i64, i64, u64, u64, u64) {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    let (days, seconds) = (seconds / 86400, seconds % 86400);
+
+    // Converts days since the epoch to a civil date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deploying_the_same_content_again_changes_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("config/fish/config.fish");
+
+        assert_eq!(
+            deploy(&target, "set -x A 1\n", None).unwrap(),
+            Outcome::Written(None)
+        );
+        assert_eq!(
+            deploy(&target, "set -x A 1\n", None).unwrap(),
+            Outcome::Unchanged
+        );
+
+        // A new mode or new contents are written, after a backup.
+        let Outcome::Written(Some(backup)) = deploy(&target, "set -x A 1\n", Some(0o600)).unwrap()
+        else {
+            panic!("the file was not backed up");
+        };
+        assert_eq!(fs::read_to_string(backup).unwrap(), "set -x A 1\n");
+        assert_eq!(
+            deploy(&target, "set -x A 1\n", Some(0o600)).unwrap(),
+            Outcome::Unchanged
+        );
+        assert!(matches!(
+            deploy(&target, "set -x A 2\n", Some(0o600)).unwrap(),
+            Outcome::Written(Some(_))
+        ));
+    }
+
+    #[test]
+    fn linking_an_already_linked_file_only_writes_the_managed_copy() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("home/.gitconfig");
+        let managed = dir.path().join("data/.gitconfig");
+        fs::create_dir_all(target.parent().unwrap()).unwrap();
+        fs::write(&target, "mine\n").unwrap();
+
+        let Outcome::Written(Some(backup)) = link(&target, &managed, "ours\n", None).unwrap()
+        else {
+            panic!("the file was not backed up");
+        };
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "mine\n");
+        assert_eq!(link_status(&target, &managed), LinkStatus::Linked);
+
+        assert_eq!(
+            link(&target, &managed, "ours\n", None).unwrap(),
+            Outcome::Unchanged
+        );
+
+        // Only the managed copy is updated, after a backup of what it had.
+        let Outcome::Written(Some(previous)) = link(&target, &managed, "new\n", None).unwrap()
+        else {
+            panic!("the managed copy was not backed up");
+        };
+        assert_eq!(fs::read_to_string(&previous).unwrap(), "ours\n");
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new\n");
+        assert_eq!(link_status(&target, &managed), LinkStatus::Linked);
+
+        restore(&target, Some(&backup), Some(&managed)).unwrap();
+        assert_eq!(fs::read_to_string(&target).unwrap(), "mine\n");
+        assert!(!managed.exists());
+    }
+
+    #[test]
+    fn written_files_get_their_mode() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("ssh/config");
+
+        // Group write is usually taken off by the umask.
+        deploy(&target, "Host *\n", Some(0o664)).unwrap();
+        let mode = fs::metadata(&target).unwrap().permissions().mode();
+        assert_eq!(mode & 0o7777, 0o664);
+
+        deploy(&target, "Host example.com\n", Some(0o600)).unwrap();
+        let mode = fs::metadata(&target).unwrap().permissions().mode();
+        assert_eq!(mode & 0o7777, 0o600);
+
+        // No temporary file is left behind.
+        let names: Vec<_> = fs::read_dir(target.parent().unwrap())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .filter(|name| !name.to_string_lossy().ends_with(".bak"))
+            .collect();
+        assert_eq!(names, ["config"]);
+    }
+}
@@ -0,0 +1,1173 @@
+// SPDX-License-Identifier: GPL-3.0
+
+//! Detects which packages are already present on a machine.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::app::models::package::Source;
+
+use super::host::user_bin_dirs;
+
+/// Whether a package is present on the machine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    Installed,
+    Missing,
+    /// Installed, but not the version the blueprint asks for.
+    Outdated {
+        installed: String,
+    },
+}
+
+/// A snapshot of the package databases of a machine.
+///
+/// Every map goes from package name to installed version, except for flatpaks which
+/// map an application id to its installed branches. Crates are those installed with
+/// `cargo install`, and rustup toolchains map their full name, such as
+/// `stable-x86_64-unknown-linux-gnu`, to their installed components. Python
+/// applications installed with pipx and `uv tool` are keyed by their normalized name,
+/// and global Node.js packages by their name, scope included. Go programs map the
+/// package they were installed from to the version of its module.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Installed {
+    pub dpkg: HashMap<String, String>,
+    pub rpm: HashMap<String, String>,
+    pub pacman: HashMap<String, String>,
+    pub flatpak: HashMap<String, Vec<String>>,
+    pub cargo: HashMap<String, String>,
+    pub rustup: HashMap<String, Vec<String>>,
+    pub pipx: HashMap<String, PythonTool>,
+    pub uv: HashMap<String, PythonTool>,
+    pub npm: HashMap<String, String>,
+    pub pnpm: HashMap<String, String>,
+    pub go: HashMap<String, String>,
+}
+
+/// A Python application installed into its own environment.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PythonTool {
+    pub version: String,
+    /// The normalized names of the other packages installed into the environment.
+    pub extras: Vec<String>,
+}
+
+impl Installed {
+    /// Reads the package databases of the system rooted at `root`.
+    ///
+    /// The dpkg and pacman databases are read straight from disk and the rpm database
+    /// is queried with `rpm --root`, so a fixture directory can stand in for the live
+    /// system. Flatpaks, crates, toolchains, Python applications, global Node.js
+    /// packages and Go programs belong to the user and are only read when `root` is
+    /// `/`.
+    pub fn load(root: &Path) -> Self {
+        let dpkg = std::fs::read_to_string(root.join("var/lib/dpkg/status"))
+            .map(|status| parse_dpkg_status(&status))
+            .unwrap_or_default();
+
+        let pacman = std::fs::read_dir(root.join("var/lib/pacman/local"))
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .filter_map(|entry| std::fs::read_to_string(entry.path().join("desc")).ok())
+                    .filter_map(|desc| parse_pacman_desc(&desc))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let rpm = root
+            .join("var/lib/rpm")
+            .exists()
+            .then(|| {
+                capture(Command::new("rpm").arg("--root").arg(root).args([
+                    "-qa",
+                    "--queryformat",
+                    "%{NAME}\\t%{VERSION}-%{RELEASE}\\n",
+                ]))
+            })
+            .flatten()
+            .map(|output| parse_rpm_query(&output))
+            .unwrap_or_default();
+
+        let flatpak = (root == Path::new("/"))
+            .then(|| {
+                capture(Command::new("flatpak").args([
+                    "list",
+                    "--app",
+                    "--columns=application,branch",
+                ]))
+            })
+            .flatten()
+            .map(|output| parse_flatpak_list(&output))
+            .unwrap_or_default();
+
+        let cargo = (root == Path::new("/"))
+            .then(|| std::fs::read_to_string(cargo_home()?.join(".crates2.json")).ok())
+            .flatten()
+            .map(|crates| parse_crates2(&crates))
+            .unwrap_or_default();
+
+        let rustup = (root == Path::new("/"))
+            .then(|| capture(user_command("rustup").args(["toolchain", "list"])))
+            .flatten()
+            .map(|output| {
+                parse_toolchains(&output)
+                    .into_iter()
+                    .map(|toolchain| {
+                        let components = capture(user_command("rustup").args([
+                            "component",
+                            "list",
+                            "--installed",
+                            "--toolchain",
+                            &toolchain,
+                        ]))
+                        .map(|output| output.lines().map(str::to_string).collect())
+                        .unwrap_or_default();
+                        (toolchain, components)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let pipx = (root == Path::new("/"))
+            .then(|| capture(user_command("pipx").args(["list", "--json"])))
+            .flatten()
+            .map(|output| parse_pipx_list(&output))
+            .unwrap_or_default();
+
+        // `--show-with` lists the extras, but only recent versions of uv have it.
+        let uv = (root == Path::new("/"))
+            .then(|| {
+                capture(user_command("uv").args(["tool", "list", "--show-with"]))
+                    .or_else(|| capture(user_command("uv").args(["tool", "list"])))
+            })
+            .flatten()
+            .map(|output| parse_uv_tool_list(&output))
+            .unwrap_or_default();
+
+        let npm = (root == Path::new("/"))
+            .then(|| global_node_modules("npm"))
+            .flatten()
+            .map(|dir| read_node_modules(&dir))
+            .unwrap_or_default();
+
+        let pnpm = (root == Path::new("/"))
+            .then(|| global_node_modules("pnpm"))
+            .flatten()
+            .map(|dir| read_node_modules(&dir))
+            .unwrap_or_default();
+
+        // `go version -m` given a directory describes every Go binary in it.
+        let go = (root == Path::new("/"))
+            .then(go_bin_dir)
+            .flatten()
+            .filter(|dir| dir.is_dir())
+            .and_then(|dir| capture(user_command("go").arg("version").arg("-m").arg(dir)))
+            .map(|output| parse_go_version(&output))
+            .unwrap_or_default();
+
+        Self {
+            dpkg,
+            rpm,
+            pacman,
+            flatpak,
+            cargo,
+            rustup,
+            pipx,
+            uv,
+            npm,
+            pnpm,
+            go,
+        }
+    }
+
+    /// The state of `source` according to this snapshot.
+    pub fn status(&self, source: &Source) -> Status {
+        let present = |db: &HashMap<String, String>, name: &str| {
+            if db.contains_key(name) {
+                Status::Installed
+            } else {
+                Status::Missing
+            }
+        };
+
+        match source {
+            Source::Apt(name) => present(&self.dpkg, name),
+            Source::Dnf(name) | Source::Zypper(name) => present(&self.rpm, name),
+            Source::Pacman(name) => present(&self.pacman, name),
+            Source::Cargo { name, version, .. } => match self.cargo.get(name) {
+                None => Status::Missing,
+                Some(installed) if matches_version(installed, version) => Status::Installed,
+                Some(installed) => Status::Outdated {
+                    installed: installed.clone(),
+                },
+            },
+            Source::Rustup {
+                toolchain,
+                targets,
+                components,
+            } => {
+                let Some((name, installed)) = self
+                    .rustup
+                    .iter()
+                    .find(|(name, _)| name.as_str() == toolchain || channel(name) == toolchain)
+                else {
+                    return Status::Missing;
+                };
+
+                // Targets are installed as their `rust-std` component, and
+                // components other than `rust-src` carry the host triple.
+                let has = |component: &str| {
+                    installed.iter().any(|installed| {
+                        installed == component
+                            || installed
+                                .strip_prefix(component)
+                                .is_some_and(|rest| rest.starts_with('-'))
+                    })
+                };
+                let complete = targets
+                    .iter()
+                    .all(|target| has(&format!("rust-std-{target}")))
+                    && components.iter().all(|component| has(component));
+
+                if complete {
+                    Status::Installed
+                } else {
+                    Status::Outdated {
+                        installed: name.clone(),
+                    }
+                }
+            }
+            Source::Pipx {
+                name,
+                version,
+                extras,
+            } => python_status(self.pipx.get(&normalize(name)), version, extras),
+            Source::Uv {
+                name,
+                version,
+                extras,
+            } => python_status(self.uv.get(&normalize(name)), version, extras),
+            Source::Npm { name, version } => node_status(self.npm.get(name), version),
+            Source::Pnpm { name, version } => node_status(self.pnpm.get(name), version),
+            Source::Go { package, version } => match self.go.get(package) {
+                None => Status::Missing,
+                Some(installed)
+                    if version == "latest"
+                        || matches_version(installed.trim_start_matches('v'), version) =>
+                {
+                    Status::Installed
+                }
+                Some(installed) => Status::Outdated {
+                    installed: installed.clone(),
+                },
+            },
+            Source::Flatpak { id, version } => match self.flatpak.get(id) {
+                None => Status::Missing,
+                Some(branches) => {
+                    if version.is_empty() || branches.contains(version) {
+                        Status::Installed
+                    } else {
+                        Status::Outdated {
+                            installed: branches.join(", "),
+                        }
+                    }
+                }
+            },
+        }
+    }
+}
+
+/// Parses `/var/lib/dpkg/status`, keeping only packages that are fully installed.
+pub fn parse_dpkg_status(status: &str) -> HashMap<String, String> {
+    status
+        .split("\n\n")
+        .filter_map(|stanza| {
+            let mut name = None;
+            let mut version = None;
+            let mut installed = false;
+
+            for line in stanza.lines() {
+                if let Some(value) = line.strip_prefix("Package:") {
+                    name = Some(value.trim().to_string());
+                } else if let Some(value) = line.strip_prefix("Version:") {
+                    version = Some(value.trim().to_string());
+                } else if let Some(value) = line.strip_prefix("Status:") {
+                    installed = value.trim().ends_with(" installed");
+                }
+            }
+
+            installed.then_some((name?, version.unwrap_or_default()))
+        })
+        .collect()
+}
+
+/// Parses a `desc` file of the pacman local database.
+pub fn parse_pacman_desc(desc: &str) -> Option<(String, String)> {
+    let mut name = None;
+    let mut version = None;
+    let mut lines = desc.lines();
+
+    while let Some(line) = lines.next() {
+        match line.trim() {
+            "%NAME%" => name = lines.next().map(|value| value.trim().to_string()),
+            "%VERSION%" => version = lines.next().map(|value| value.trim().to_string()),
+            _ => {}
+        }
+    }
+
+    Some((name?, version.unwrap_or_default()))
+}
+
+/// Parses the output of `rpm -qa --queryformat '%{NAME}\t%{VERSION}-%{RELEASE}\n'`.
+pub fn parse_rpm_query(output: &str) -> HashMap<String, String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (name, version) = line.split_once('\t')?;
+            Some((name.trim().to_string(), version.trim().to_string()))
+        })
+        .collect()
+}
+
+/// Parses the output of `flatpak list --app --columns=application,branch`.
+pub fn parse_flatpak_list(output: &str) -> HashMap<String, Vec<String>> {
+    let mut apps: HashMap<String, Vec<String>> = HashMap::new();
+
+    for line in output.lines() {
+        let mut columns = line.split('\t').map(str::trim);
+        let Some(id) = columns.next().filter(|id| !id.is_empty()) else {
+            continue;
+        };
+        let branch = columns.next().unwrap_or_default().to_string();
+
+        apps.entry(id.to_string()).or_default().push(branch);
+    }
+
+    apps
+}
+
+/// Parses `.crates2.json` in the cargo home, which lists the crates installed with
+/// `cargo install` under keys such as
+/// `ripgrep 14.1.0 (registry+https://github.com/rust-lang/crates.io-index)`.
+pub fn parse_crates2(crates: &str) -> HashMap<String, String> {
+    let Ok(crates) = serde_json::from_str::<serde_json::Value>(crates) else {
+        return HashMap::new();
+    };
+
+    crates["installs"]
+        .as_object()
+        .into_iter()
+        .flat_map(|installs| installs.keys())
+        .filter_map(|key| {
+            let mut parts = key.split_whitespace();
+            Some((parts.next()?.to_string(), parts.next()?.to_string()))
+        })
+        .collect()
+}
+
+/// Where cargo keeps installed binaries and their records: `$CARGO_HOME`, by default
+/// `~/.cargo`.
+pub fn cargo_home() -> Option<PathBuf> {
+    std::env::var_os("CARGO_HOME")
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| Some(dirs::home_dir()?.join(".cargo")))
+}
+
+/// Parses `rustup toolchain list`, which prints one toolchain per line followed by
+/// markers such as `(default)`.
+pub fn parse_toolchains(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter(|line| !line.starts_with("no installed toolchains"))
+        .filter_map(|line| line.split_whitespace().next())
+        .map(str::to_string)
+        .collect()
+}
+
+/// The channel of a toolchain without its host triple: `stable` for
+/// `stable-x86_64-unknown-linux-gnu` and `nightly-2024-05-01` for
+/// `nightly-2024-05-01-x86_64-unknown-linux-gnu`.
+pub fn channel(toolchain: &str) -> &str {
+    // The date of a channel is the only part of the name that starts with a digit.
+    let mut end = toolchain.find('-').unwrap_or(toolchain.len());
+    while let Some(rest) = toolchain[end..].strip_prefix('-') {
+        if !rest.starts_with(|c: char| c.is_ascii_digit()) {
+            break;
+        }
+        end += 1 + rest.find('-').unwrap_or(rest.len());
+    }
+    &toolchain[..end]
+}
+
+/// Parses `pipx list --json`, which describes the environment of each application
+/// with its main package and the packages injected into it.
+pub fn parse_pipx_list(list: &str) -> HashMap<String, PythonTool> {
+    let Ok(list) = serde_json::from_str::<serde_json::Value>(list) else {
+        return HashMap::new();
+    };
+
+    list["venvs"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(venv, info)| {
+            let metadata = &info["metadata"];
+            let main = &metadata["main_package"];
+            let name = main["package"].as_str().unwrap_or(venv);
+            let tool = PythonTool {
+                version: main["package_version"].as_str()?.to_string(),
+                extras: metadata["injected_packages"]
+                    .as_object()
+                    .into_iter()
+                    .flat_map(|injected| injected.keys())
+                    .map(|extra| normalize(extra))
+                    .collect(),
+            };
+            Some((normalize(name), tool))
+        })
+        .collect()
+}
+
+/// Parses `uv tool list`, which prints each application as `black v24.8.0`,
+/// followed by `[with: requests]` when given `--show-with`, and then its executables
+/// on lines starting with `-`.
+pub fn parse_uv_tool_list(list: &str) -> HashMap<String, PythonTool> {
+    list.lines()
+        .filter(|line| !line.starts_with('-') && !line.trim().is_empty())
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let name = parts.next()?;
+            let version = parts.next()?.trim_start_matches('v').to_string();
+            let extras = line
+                .split_once("[with: ")
+                .and_then(|(_, rest)| rest.split_once(']'))
+                .map(|(with, _)| with.split(',').map(normalize).collect())
+                .unwrap_or_default();
+            Some((normalize(name), PythonTool { version, extras }))
+        })
+        .collect()
+}
+
+/// Python package names compare equal regardless of case and of `-`, `_` and `.`.
+/// Anything after the name, such as a version specifier, is dropped.
+fn normalize(requirement: &str) -> String {
+    requirement
+        .trim()
+        .split(['<', '>', '=', '!', '~', '[', ';', ' '])
+        .next()
+        .unwrap_or_default()
+        .to_lowercase()
+        .replace(['_', '.'], "-")
+}
+
+fn python_status(tool: Option<&PythonTool>, version: &str, extras: &[String]) -> Status {
+    let Some(tool) = tool else {
+        return Status::Missing;
+    };
+
+    // Ranges such as `>=24` are left to the installer; only exact versions are
+    // compared.
+    let pinned = version.strip_prefix("==").unwrap_or(version);
+    let version_matches =
+        pinned.starts_with(['<', '>', '=', '!', '~']) || matches_version(&tool.version, pinned);
+    let has_extras = extras
+        .iter()
+        .all(|extra| tool.extras.contains(&normalize(extra)));
+
+    if version_matches && has_extras {
+        Status::Installed
+    } else {
+        Status::Outdated {
+            installed: tool.version.clone(),
+        }
+    }
+}
+
+/// The global `node_modules` directory of `program`, npm or pnpm.
+pub(crate) fn global_node_modules(program: &str) -> Option<PathBuf> {
+    let output = capture(user_command(program).args(["root", "--global"]))?;
+    let dir = PathBuf::from(output.trim());
+    dir.is_dir().then_some(dir)
+}
+
+/// Reads the name and version of every package in a `node_modules` directory from
+/// its `package.json`, looking into `@scope` directories.
+pub fn read_node_modules(dir: &Path) -> HashMap<String, String> {
+    let mut packages = HashMap::new();
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return packages;
+    };
+
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        // `.bin` holds the executables and `.pnpm` the store of pnpm.
+        if name.starts_with('.') {
+            continue;
+        }
+
+        if name.starts_with('@') {
+            packages.extend(
+                read_node_modules(&entry.path())
+                    .into_iter()
+                    .map(|(package, version)| (format!("{name}/{package}"), version)),
+            );
+            continue;
+        }
+
+        let version = std::fs::read_to_string(entry.path().join("package.json"))
+            .ok()
+            .and_then(|manifest| serde_json::from_str::<serde_json::Value>(&manifest).ok())
+            .and_then(|manifest| manifest["version"].as_str().map(str::to_string));
+        if let Some(version) = version {
+            packages.insert(name, version);
+        }
+    }
+
+    packages
+}
+
+fn node_status(installed: Option<&String>, version: &str) -> Status {
+    let Some(installed) = installed else {
+        return Status::Missing;
+    };
+
+    // Ranges such as `^5` and tags such as `next` are left to the package manager;
+    // only exact versions are compared.
+    let exact = version.trim_start_matches(['=', 'v']);
+    if !exact.starts_with(|c: char| c.is_ascii_digit())
+        || exact.contains([' ', '|', '<', '>', 'x', '*'])
+        || matches_version(installed, exact)
+    {
+        Status::Installed
+    } else {
+        Status::Outdated {
+            installed: installed.clone(),
+        }
+    }
+}
+
+/// Parses `go version -m`, which prints the package each binary was built from on
+/// a `path` line and the version of its module on the `mod` line after it.
+pub fn parse_go_version(output: &str) -> HashMap<String, String> {
+    let mut programs = HashMap::new();
+    let mut package = None;
+
+    for line in output.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields.as_slice() {
+            ["path", path] => package = Some(path.to_string()),
+            ["mod", _, version, ..] => {
+                if let Some(package) = package.take() {
+                    programs.insert(package, version.to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    programs
+}
+
+/// Where `go install` puts binaries: `$GOBIN`, by default the `bin` directory of
+/// the first entry of `$GOPATH`, itself `~/go` by default.
+pub(crate) fn go_bin_dir() -> Option<PathBuf> {
+    // Asks go first, since both can also be set with `go env -w`.
+    if let Some(output) = capture(user_command("go").args(["env", "GOBIN", "GOPATH"])) {
+        let mut lines = output.lines();
+        if let Some(gobin) = lines.next().filter(|gobin| !gobin.is_empty()) {
+            return Some(PathBuf::from(gobin));
+        }
+        if let Some(gopath) = lines
+            .next()
+            .and_then(|path| std::env::split_paths(path).next())
+        {
+            return Some(gopath.join("bin"));
+        }
+    }
+
+    match std::env::var_os("GOBIN").filter(|gobin| !gobin.is_empty()) {
+        Some(gobin) => Some(PathBuf::from(gobin)),
+        None => std::env::var_os("GOPATH")
+            .and_then(|path| std::env::split_paths(&path).next())
+            .filter(|gopath| !gopath.as_os_str().is_empty())
+            .or_else(|| Some(dirs::home_dir()?.join("go")))
+            .map(|gopath| gopath.join("bin")),
+    }
+}
+
+/// The binary `go install` makes for `package`: its last element, skipping a major
+/// version suffix such as `/v2`.
+pub fn go_binary(package: &str) -> &str {
+    let mut elements = package.rsplit('/');
+    let last = elements.next().unwrap_or(package);
+    let is_major = last
+        .strip_prefix('v')
+        .is_some_and(|major| !major.is_empty() && major.bytes().all(|b| b.is_ascii_digit()));
+    match elements.next() {
+        Some(parent) if is_major => parent,
+        _ => last,
+    }
+}
+
+/// A command for `program`, found among the user's own executables when it is not
+/// on the path.
+pub(crate) fn user_command(program: &str) -> Command {
+    user_bin_dirs()
+        .into_iter()
+        .map(|dir| dir.join(program))
+        .find(|path| path.is_file())
+        .map(Command::new)
+        .unwrap_or_else(|| Command::new(program))
+}
+
+/// Whether the `installed` version satisfies `wanted`, which may be empty to accept
+/// any, a full version, or a prefix such as `14` or `14.1`.
+fn matches_version(installed: &str, wanted: &str) -> bool {
+    let wanted = wanted.trim().trim_start_matches(['=', '^', 'v']);
+    wanted.is_empty()
+        || installed == wanted
+        || installed
+            .strip_prefix(wanted)
+            .is_some_and(|rest| rest.starts_with('.'))
+}
+
+/// Runs `command` and returns its standard output if it succeeded.
+pub(crate) fn capture(command: &mut Command) -> Option<String> {
+    let output = command.output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::models::package::Kind;
+
+    use super::*;
+
+    const DPKG_STATUS: &str = "\
+Package: fish
+Status: install ok installed
+Priority: optional
+Version: 3.7.0-1
+
+Package: zsh
+Status: deinstall ok config-files
+Version: 5.9-4
+
+Package: git
+Status: install ok installed
+Version: 1:2.43.0-1ubuntu7
+";
+
+    const PACMAN_DESC: &str = "\
+%NAME%
+neovim
+
+%VERSION%
+0.10.0-1
+
+%BASE%
+neovim
+";
+
+    /// A source of `kind` for `name`, pinned to `version` unless it is empty.
+    fn pinned(kind: Kind, name: &str, version: &str) -> Source {
+        let mut source = kind.source(name);
+        if let Some(pinned) = source.version_mut() {
+            *pinned = version.to_string();
+        }
+        source
+    }
+
+    fn fixture_root() -> tempfile::TempDir {
+        let root = tempfile::tempdir().unwrap();
+        let dpkg = root.path().join("var/lib/dpkg");
+        std::fs::create_dir_all(&dpkg).unwrap();
+        std::fs::write(dpkg.join("status"), DPKG_STATUS).unwrap();
+
+        let pacman = root.path().join("var/lib/pacman/local/neovim-0.10.0-1");
+        std::fs::create_dir_all(&pacman).unwrap();
+        std::fs::write(pacman.join("desc"), PACMAN_DESC).unwrap();
+        root
+    }
+
+    #[test]
+    fn dpkg_status_keeps_installed_packages() {
+        let packages = parse_dpkg_status(DPKG_STATUS);
+        assert_eq!(packages.get("fish").map(String::as_str), Some("3.7.0-1"));
+        assert_eq!(
+            packages.get("git").map(String::as_str),
+            Some("1:2.43.0-1ubuntu7")
+        );
+        assert!(!packages.contains_key("zsh"));
+    }
+
+    #[test]
+    fn pacman_desc() {
+        assert_eq!(
+            parse_pacman_desc(PACMAN_DESC),
+            Some(("neovim".to_string(), "0.10.0-1".to_string()))
+        );
+        assert_eq!(parse_pacman_desc("%VERSION%\n1.0\n"), None);
+    }
+
+    #[test]
+    fn rpm_query() {
+        let packages = parse_rpm_query("fish\t3.7.0-1.fc40\nbroken line\ngit\t2.45.2-2.fc40\n");
+        assert_eq!(packages.len(), 2);
+        assert_eq!(packages["fish"], "3.7.0-1.fc40");
+    }
+
+    #[test]
+    fn flatpak_list_groups_branches() {
+        let apps = parse_flatpak_list(
+            "org.mozilla.firefox\tstable\norg.gnome.Builder\tstable\norg.gnome.Builder\tmaster\n\n",
+        );
+        assert_eq!(apps["org.mozilla.firefox"], ["stable"]);
+        assert_eq!(apps["org.gnome.Builder"], ["stable", "master"]);
+    }
+
+    #[test]
+    fn load_reads_fixture_root() {
+        let root = fixture_root();
+        let installed = Installed::load(root.path());
+
+        assert_eq!(
+            installed.status(&Source::Apt("fish".into())),
+            Status::Installed
+        );
+        assert_eq!(
+            installed.status(&Source::Apt("zsh".into())),
+            Status::Missing
+        );
+        assert_eq!(
+            installed.status(&Source::Pacman("neovim".into())),
+            Status::Installed
+        );
+        assert_eq!(
+            installed.status(&Source::Pacman("fish".into())),
+            Status::Missing
+        );
+        // The rpm database and the user's own packages are not in the fixture.
+        assert!(installed.rpm.is_empty());
+        assert!(installed.flatpak.is_empty());
+    }
+
+    #[test]
+    fn flatpak_branch_mismatch_is_outdated() {
+        let installed = Installed {
+            flatpak: parse_flatpak_list("org.gnome.Builder\tstable\n"),
+            ..Default::default()
+        };
+        let source = |version: &str| Source::Flatpak {
+            id: "org.gnome.Builder".into(),
+            version: version.into(),
+        };
+
+        assert_eq!(installed.status(&source("")), Status::Installed);
+        assert_eq!(installed.status(&source("stable")), Status::Installed);
+        assert_eq!(
+            installed.status(&source("master")),
+            Status::Outdated {
+                installed: "stable".into()
+            }
+        );
+    }
+
+    #[test]
+    fn crates2() {
+        let crates = parse_crates2(
+            r#"{
+  "installs": {
+    "ripgrep 14.1.0 (registry+https://github.com/rust-lang/crates.io-index)": {
+      "version_req": null,
+      "bins": ["rg"],
+      "features": [],
+      "all_features": false,
+      "no_default_features": false,
+      "profile": "release",
+      "target": "x86_64-unknown-linux-gnu",
+      "rustc": "rustc 1.80.0"
+    },
+    "tokei 13.0.0-alpha.1 (git+https://github.com/XAMPPRocky/tokei#0123abcd)": {}
+  }
+}"#,
+        );
+        assert_eq!(crates.len(), 2);
+        assert_eq!(crates["ripgrep"], "14.1.0");
+        assert_eq!(crates["tokei"], "13.0.0-alpha.1");
+
+        assert!(parse_crates2("").is_empty());
+        assert!(parse_crates2("{\"v1\": {}}").is_empty());
+    }
+
+    #[test]
+    fn cargo_versions() {
+        let installed = Installed {
+            cargo: HashMap::from([("ripgrep".to_string(), "14.1.0".to_string())]),
+            ..Default::default()
+        };
+        let status = |version: &str| installed.status(&pinned(Kind::Cargo, "ripgrep", version));
+
+        assert_eq!(status(""), Status::Installed);
+        assert_eq!(status("14"), Status::Installed);
+        assert_eq!(status("14.1"), Status::Installed);
+        assert_eq!(status("=14.1.0"), Status::Installed);
+        assert_eq!(
+            status("14.2"),
+            Status::Outdated {
+                installed: "14.1.0".into()
+            }
+        );
+        // `1` is not a prefix of `14.1.0`.
+        assert_eq!(
+            status("1"),
+            Status::Outdated {
+                installed: "14.1.0".into()
+            }
+        );
+        assert_eq!(
+            installed.status(&pinned(Kind::Cargo, "tokei", "")),
+            Status::Missing
+        );
+    }
+
+    #[test]
+    fn toolchains() {
+        let output = "\
+stable-x86_64-unknown-linux-gnu (default)
+nightly-2024-05-01-x86_64-unknown-linux-gnu
+1.79.0-x86_64-unknown-linux-gnu (override)
+";
+        assert_eq!(
+            parse_toolchains(output),
+            [
+                "stable-x86_64-unknown-linux-gnu",
+                "nightly-2024-05-01-x86_64-unknown-linux-gnu",
+                "1.79.0-x86_64-unknown-linux-gnu",
+            ]
+        );
+        assert!(parse_toolchains("no installed toolchains\n").is_empty());
+    }
+
+    #[test]
+    fn channels() {
+        assert_eq!(channel("stable-x86_64-unknown-linux-gnu"), "stable");
+        assert_eq!(
+            channel("nightly-2024-05-01-x86_64-unknown-linux-gnu"),
+            "nightly-2024-05-01"
+        );
+        assert_eq!(channel("beta-aarch64-unknown-linux-musl"), "beta");
+        assert_eq!(channel("1.79.0-x86_64-unknown-linux-gnu"), "1.79.0");
+        assert_eq!(channel("stable"), "stable");
+    }
+
+    #[test]
+    fn rustup_components_and_targets() {
+        let installed = Installed {
+            rustup: HashMap::from([(
+                "stable-x86_64-unknown-linux-gnu".to_string(),
+                vec![
+                    "cargo-x86_64-unknown-linux-gnu".to_string(),
+                    "clippy-x86_64-unknown-linux-gnu".to_string(),
+                    "rust-src".to_string(),
+                    "rust-std-wasm32-unknown-unknown".to_string(),
+                    "rust-std-x86_64-unknown-linux-gnu".to_string(),
+                ],
+            )]),
+            ..Default::default()
+        };
+        let status = |toolchain: &str, targets: &[&str], components: &[&str]| {
+            installed.status(&Source::Rustup {
+                toolchain: toolchain.into(),
+                targets: targets.iter().map(|target| target.to_string()).collect(),
+                components: components
+                    .iter()
+                    .map(|component| component.to_string())
+                    .collect(),
+            })
+        };
+        let outdated = Status::Outdated {
+            installed: "stable-x86_64-unknown-linux-gnu".into(),
+        };
+
+        assert_eq!(status("stable", &[], &[]), Status::Installed);
+        assert_eq!(
+            status("stable-x86_64-unknown-linux-gnu", &[], &[]),
+            Status::Installed
+        );
+        assert_eq!(
+            status(
+                "stable",
+                &["wasm32-unknown-unknown"],
+                &["clippy", "rust-src"]
+            ),
+            Status::Installed
+        );
+        assert_eq!(status("stable", &[], &["rustfmt"]), outdated);
+        assert_eq!(
+            status("stable", &["aarch64-unknown-linux-gnu"], &[]),
+            outdated
+        );
+        assert_eq!(status("nightly", &[], &[]), Status::Missing);
+        assert_eq!(status("1.79.0", &[], &[]), Status::Missing);
+    }
+
+    #[test]
+    fn pipx_list() {
+        let tools = parse_pipx_list(
+            r#"{
+  "pipx_spec_version": "0.1",
+  "venvs": {
+    "black": {
+      "metadata": {
+        "main_package": { "package": "black", "package_version": "24.8.0" },
+        "injected_packages": {
+          "Foo_Bar": { "package": "Foo_Bar", "package_version": "1.0" }
+        }
+      }
+    },
+    "Jupyter.Core": {
+      "metadata": {
+        "main_package": { "package": "Jupyter.Core", "package_version": "5.7.2" },
+        "injected_packages": {}
+      }
+    },
+    "broken": { "metadata": {} }
+  }
+}"#,
+        );
+        assert_eq!(tools.len(), 2);
+        assert_eq!(
+            tools["black"],
+            PythonTool {
+                version: "24.8.0".into(),
+                extras: vec!["foo-bar".into()],
+            }
+        );
+        assert_eq!(tools["jupyter-core"].version, "5.7.2");
+        assert!(parse_pipx_list("not json").is_empty());
+    }
+
+    #[test]
+    fn uv_tool_list() {
+        let tools = parse_uv_tool_list(
+            "\
+black v24.8.0 [with: Foo_Bar, requests>=2]
+- black
+- blackd
+ruff v0.6.4
+- ruff
+",
+        );
+        assert_eq!(tools.len(), 2);
+        assert_eq!(
+            tools["black"],
+            PythonTool {
+                version: "24.8.0".into(),
+                extras: vec!["foo-bar".into(), "requests".into()],
+            }
+        );
+        assert!(tools["ruff"].extras.is_empty());
+    }
+
+    #[test]
+    fn python_names_are_normalized() {
+        assert_eq!(normalize("Foo_Bar"), "foo-bar");
+        assert_eq!(normalize("foo-bar"), "foo-bar");
+        assert_eq!(normalize("Foo.Bar"), "foo-bar");
+        assert_eq!(normalize(" black[d]>=24 "), "black");
+        assert_eq!(normalize("requests==2.32.3"), "requests");
+        assert_eq!(normalize("tomli; python_version < '3.11'"), "tomli");
+    }
+
+    #[test]
+    fn python_versions_and_extras() {
+        let installed = Installed {
+            pipx: parse_pipx_list(
+                r#"{"venvs": {"foo-bar": {"metadata": {
+                    "main_package": {"package": "foo-bar", "package_version": "1.2.3"},
+                    "injected_packages": {"Requests": {}}
+                }}}}"#,
+            ),
+            uv: parse_uv_tool_list("foo-bar v1.2.3\n"),
+            ..Default::default()
+        };
+        let outdated = Status::Outdated {
+            installed: "1.2.3".into(),
+        };
+
+        for kind in [Kind::Pipx, Kind::Uv] {
+            let status = |version: &str| installed.status(&pinned(kind, "Foo_Bar", version));
+            assert_eq!(status(""), Status::Installed, "{kind}");
+            assert_eq!(status("1.2"), Status::Installed, "{kind}");
+            assert_eq!(status("==1.2.3"), Status::Installed, "{kind}");
+            // Ranges are left to the installer.
+            assert_eq!(status(">=2"), Status::Installed, "{kind}");
+            assert_eq!(status("1.3"), outdated, "{kind}");
+            assert_eq!(
+                installed.status(&pinned(kind, "black", "")),
+                Status::Missing,
+                "{kind}"
+            );
+        }
+
+        let with = |extras: &[&str]| Source::Pipx {
+            name: "foo-bar".into(),
+            version: String::new(),
+            extras: extras.iter().map(|extra| extra.to_string()).collect(),
+        };
+        assert_eq!(installed.status(&with(&["requests"])), Status::Installed);
+        assert_eq!(installed.status(&with(&["requests", "rich"])), outdated);
+    }
+
+    #[test]
+    fn node_modules() {
+        let dir = tempfile::tempdir().unwrap();
+        let package = |path: &str, manifest: &str| {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(&path).unwrap();
+            std::fs::write(path.join("package.json"), manifest).unwrap();
+        };
+        package(
+            "typescript",
+            r#"{"name": "typescript", "version": "5.5.4"}"#,
+        );
+        package(
+            "@angular/cli",
+            r#"{"name": "@angular/cli", "version": "18.2.1"}"#,
+        );
+        package("no-version", r#"{"name": "no-version"}"#);
+        package("broken", "{");
+        package(".pnpm/typescript@5.5.4", r#"{"version": "5.5.4"}"#);
+        std::fs::create_dir_all(dir.path().join(".bin")).unwrap();
+
+        let packages = read_node_modules(dir.path());
+        assert_eq!(
+            packages,
+            HashMap::from([
+                ("typescript".to_string(), "5.5.4".to_string()),
+                ("@angular/cli".to_string(), "18.2.1".to_string()),
+            ])
+        );
+        assert!(read_node_modules(&dir.path().join("missing")).is_empty());
+    }
+
+    #[test]
+    fn node_versions() {
+        let installed = Installed {
+            npm: HashMap::from([("typescript".to_string(), "5.5.4".to_string())]),
+            pnpm: HashMap::from([("typescript".to_string(), "5.5.4".to_string())]),
+            ..Default::default()
+        };
+        let outdated = Status::Outdated {
+            installed: "5.5.4".into(),
+        };
+
+        for kind in [Kind::Npm, Kind::Pnpm] {
+            let status = |version: &str| installed.status(&pinned(kind, "typescript", version));
+            assert_eq!(status(""), Status::Installed, "{kind}");
+            assert_eq!(status("5.5.4"), Status::Installed, "{kind}");
+            assert_eq!(status("v5.5"), Status::Installed, "{kind}");
+            assert_eq!(status("5.4.5"), outdated, "{kind}");
+            // Ranges and tags are left to the package manager.
+            assert_eq!(status("^4"), Status::Installed, "{kind}");
+            assert_eq!(status("5.x"), Status::Installed, "{kind}");
+            assert_eq!(status("next"), Status::Installed, "{kind}");
+            assert_eq!(
+                installed.status(&pinned(kind, "eslint", "")),
+                Status::Missing,
+                "{kind}"
+            );
+        }
+    }
+
+    #[test]
+    fn go_version() {
+        let output = "\
+/home/me/go/bin/gopls: go1.22.5
+\tpath\tgolang.org/x/tools/gopls
+\tmod\tgolang.org/x/tools/gopls\tv0.16.1\th1:abc=
+\tdep\tgithub.com/BurntSushi/toml\tv1.4.0\th1:def=
+\tbuild\t-compiler=gc
+/home/me/go/bin/staticcheck: go1.22.5
+\tpath\thonnef.co/go/tools/cmd/staticcheck
+\tmod\thonnef.co/go/tools\tv0.4.7\th1:ghi=
+/home/me/go/bin/local: go1.22.5
+\tpath\texample.com/local
+\tmod\texample.com/local\t(devel)\t
+";
+        let programs = parse_go_version(output);
+        assert_eq!(
+            programs,
+            HashMap::from([
+                (
+                    "golang.org/x/tools/gopls".to_string(),
+                    "v0.16.1".to_string()
+                ),
+                (
+                    "honnef.co/go/tools/cmd/staticcheck".to_string(),
+                    "v0.4.7".to_string()
+                ),
+                ("example.com/local".to_string(), "(devel)".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn versions_match_by_prefix() {
+        assert!(matches_version("14.1.0", ""));
+        assert!(matches_version("14.1.0", "14.1.0"));
+        assert!(matches_version("14.1.0", "14"));
+        assert!(matches_version("14.1.0", "14.1"));
+        assert!(matches_version("14.1.0", "=14.1.0"));
+        assert!(matches_version("14.1.0", "^14"));
+        assert!(matches_version("0.16.1", "v0.16"));
+        assert!(!matches_version("14.1.0", "1"));
+        assert!(!matches_version("14.1.0", "14.1.0.1"));
+        assert!(!matches_version("14.10.0", "14.1"));
+    }
+
+    #[test]
+    fn go_versions() {
+        let installed = Installed {
+            go: HashMap::from([(
+                "golang.org/x/tools/gopls".to_string(),
+                "v0.16.1".to_string(),
+            )]),
+            ..Default::default()
+        };
+        let status = |version: &str| {
+            installed.status(&pinned(Kind::Go, "golang.org/x/tools/gopls", version))
+        };
+
+        assert_eq!(status(""), Status::Installed);
+        assert_eq!(status("latest"), Status::Installed);
+        assert_eq!(status("v0.16.1"), Status::Installed);
+        assert_eq!(status("v0.16"), Status::Installed);
+        assert_eq!(
+            status("v0.15.3"),
+            Status::Outdated {
+                installed: "v0.16.1".into()
+            }
+        );
+        assert_eq!(
+            installed.status(&pinned(Kind::Go, "golang.org/x/tools/cmd/goimports", "")),
+            Status::Missing
+        );
+    }
+
+    #[test]
+    fn go_binaries() {
+        assert_eq!(go_binary("golang.org/x/tools/gopls"), "gopls");
+        assert_eq!(go_binary("github.com/go-delve/delve/cmd/dlv"), "dlv");
+        assert_eq!(go_binary("github.com/example/tool/v2"), "tool");
+        assert_eq!(go_binary("example.com/v"), "v");
+        assert_eq!(go_binary("tool"), "tool");
+    }
+}
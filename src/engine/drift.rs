@@ -0,0 +1,166 @@
+// SPDX-License-Identifier: GPL-3.0
+
+//! Finds config files that were changed on disk after being deployed.
+
+use std::path::PathBuf;
+
+use similar::{ChangeTag, TextDiff};
+
+use crate::app::models::package::{DeployMethod, Package};
+
+use super::deploy::{self, LinkStatus};
+use super::revert::Applied;
+use super::template::Variables;
+
+/// A config file whose contents on disk differ from the blueprint's.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Drift {
+    /// The index of the file in the package's config files.
+    pub index: usize,
+    pub target: PathBuf,
+    /// What the blueprint would write, with placeholders filled in.
+    pub expected: String,
+    /// What the file contains on disk.
+    pub current: String,
+}
+
+/// A line of a side-by-side diff. A side is empty where the other one has a line
+/// that was added or removed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Row {
+    pub left: Option<String>,
+    pub right: Option<String>,
+}
+
+impl Row {
+    pub fn is_changed(&self) -> bool {
+        self.left != self.right
+    }
+}
+
+/// The config files of `package` that drifted from the blueprint.
+///
+/// Only files Blueprint wrote for `package`, as recorded in `applied`, are compared:
+/// others have not been deployed yet, or belong to the user. Linked files only
+/// drift once their link was replaced by a regular file, since edits made through
+/// the link are pulled into the blueprint. Templates are compared once rendered with
+/// `variables` and merged files once merged, and skipped if they cannot be.
+pub fn detect(package: &Package, applied: &Applied, variables: &Variables) -> Vec<Drift> {
+    if !package.enabled {
+        return vec![];
+    }
+
+    package
+        .config
+        .iter()
+        .enumerate()
+        .filter_map(|(index, file)| {
+            let target = file.target_path();
+            if !applied
+                .files
+                .iter()
+                .any(|deployed| deployed.package == package.id && deployed.target == target)
+            {
+                return None;
+            }
+
+            if file.method == DeployMethod::Symlink {
+                let managed = deploy::managed_path(package.id, &target)?;
+                if deploy::link_status(&target, &managed) != LinkStatus::Replaced {
+                    return None;
+                }
+            }
+
+            let current = std::fs::read_to_string(&target).ok()?;
+            let expected = deploy::expected(file, Some(&current), variables).ok()?;
+            (current != expected).then_some(Drift {
+                index,
+                target,
+                expected,
+                current,
+            })
+        })
+        .collect()
+}
+
+/// Lines `old` and `new` next to each other, pairing removed lines with the lines
+/// added in their place.
+pub fn side_by_side(old: &str, new: &str) -> Vec<Row> {
+    let diff = TextDiff::from_lines(old, new);
+    let mut rows = vec![];
+    let (mut removed, mut added) = (vec![], vec![]);
+
+    for change in diff.iter_all_changes() {
+        let line = change.value().trim_end_matches('\n').to_string();
+        match change.tag() {
+            ChangeTag::Delete => removed.push(line),
+            ChangeTag::Insert => added.push(line),
+            ChangeTag::Equal => {
+                flush(&mut rows, &mut removed, &mut added);
+                rows.push(Row {
+                    left: Some(line.clone()),
+                    right: Some(line),
+                });
+            }
+        }
+    }
+
+    flush(&mut rows, &mut removed, &mut added);
+    rows
+}
+
+fn flush(rows: &mut Vec<Row>, removed: &mut Vec<String>, added: &mut Vec<String>) {
+    let count = removed.len().max(added.len());
+    let mut removed = removed.drain(..);
+    let mut added = added.drain(..);
+
+    for _ in 0..count {
+        rows.push(Row {
+            left: removed.next(),
+            right: added.next(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::models::package::{ConfigFile, Source};
+    use crate::app::Page;
+
+    use super::*;
+
+    fn package(target: PathBuf) -> Package {
+        let mut package = Package::new("fish", Source::Apt("fish".into()), Page::Shells);
+        package
+            .config
+            .push(ConfigFile::new(target, "set -g fish_greeting\n"));
+        package
+    }
+
+    #[test]
+    fn only_deployed_files_drift() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("config.fish");
+        std::fs::write(&target, "set -g fish_greeting hello\n").unwrap();
+        let package = package(target.clone());
+        let variables = Variables::new();
+
+        // A file the user had before Blueprint ever wrote it is theirs.
+        assert!(detect(&package, &Applied::default(), &variables).is_empty());
+
+        let mut applied = Applied::default();
+        applied.files.push(deploy::Deployment {
+            package: package.id,
+            target: target.clone(),
+            backup: None,
+        });
+        let drift = detect(&package, &applied, &variables);
+        assert_eq!(drift.len(), 1);
+        assert_eq!(drift[0].target, target);
+        assert_eq!(drift[0].current, "set -g fish_greeting hello\n");
+
+        // The same target written for another package does not count.
+        applied.files[0].package = uuid::Uuid::new_v4();
+        assert!(detect(&package, &applied, &variables).is_empty());
+    }
+}
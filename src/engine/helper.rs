@@ -0,0 +1,355 @@
+// SPDX-License-Identifier: GPL-3.0
+
+//! Runs the system package manager calls of a run as root through one helper
+//! process, so the user authenticates once per run rather than once per package.
+//!
+//! The helper is this executable started with `pkexec` or `sudo` as
+//! `blueprint helper`. It reads one [`Request`] per line on stdin, builds the
+//! package manager call itself, and answers each with the lines it printed followed
+//! by how it exited, as JSON. It never runs a command it is sent.
+
+use std::process::{ExitCode, Stdio};
+
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader, Lines};
+use tokio::process::{Child, ChildStdin, ChildStdout};
+
+use crate::app::models::package::Source;
+
+use super::installer::Invocation;
+
+/// How the helper gets root.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Elevation {
+    /// Through polkit, which asks for the password in a desktop dialog.
+    #[default]
+    Pkexec,
+    /// Through sudo, which asks for the password on the terminal.
+    Sudo,
+}
+
+/// What the helper is asked to do.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Request {
+    pub verb: Verb,
+    pub manager: Manager,
+    pub names: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Verb {
+    Install,
+    Remove,
+}
+
+/// The package managers the helper runs as root.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Manager {
+    Apt,
+    Dnf,
+    Pacman,
+    Zypper,
+}
+
+/// A message from the helper about the invocation it is running.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Response {
+    /// A line the invocation printed.
+    Output(String),
+    /// The invocation exited, with its code unless a signal ended it.
+    Exited(Option<i32>),
+    /// The invocation could not be started.
+    Error(String),
+}
+
+/// A running helper.
+pub struct Helper {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+}
+
+impl Elevation {
+    pub fn program(self) -> &'static str {
+        match self {
+            Elevation::Pkexec => "pkexec",
+            Elevation::Sudo => "sudo",
+        }
+    }
+}
+
+impl Request {
+    /// A request to `verb` the package of `source`, if its package manager is one
+    /// the helper runs.
+    pub fn new(verb: Verb, source: &Source) -> Option<Self> {
+        let (manager, name) = match source {
+            Source::Apt(name) => (Manager::Apt, name),
+            Source::Dnf(name) => (Manager::Dnf, name),
+            Source::Pacman(name) => (Manager::Pacman, name),
+            Source::Zypper(name) => (Manager::Zypper, name),
+            _ => return None,
+        };
+
+        Some(Self {
+            verb,
+            manager,
+            names: vec![name.clone()],
+        })
+    }
+
+    /// Refuses requests without names, and names the package manager could take
+    /// for an option, a path or several arguments.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.names.is_empty() {
+            return Err("no package was given".to_string());
+        }
+
+        let invalid = self.names.iter().find(|name| {
+            name.is_empty()
+                || name.starts_with('-')
+                || name.contains(|c: char| c == '/' || c.is_whitespace())
+        });
+        match invalid {
+            Some(name) => Err(format!("\"{name}\" is not a package name")),
+            None => Ok(()),
+        }
+    }
+
+    /// The package manager call that carries the request out.
+    pub fn invocation(&self) -> Invocation {
+        let (program, args): (&str, &[&str]) = match (self.manager, self.verb) {
+            (Manager::Apt, Verb::Install) => ("apt-get", &["install", "-y"]),
+            (Manager::Apt, Verb::Remove) => ("apt-get", &["remove", "-y"]),
+            (Manager::Dnf, Verb::Install) => ("dnf", &["install", "-y"]),
+            (Manager::Dnf, Verb::Remove) => ("dnf", &["remove", "-y"]),
+            (Manager::Pacman, Verb::Install) => ("pacman", &["-S", "--needed", "--noconfirm"]),
+            (Manager::Pacman, Verb::Remove) => ("pacman", &["-R", "--noconfirm"]),
+            (Manager::Zypper, Verb::Install) => ("zypper", &["--non-interactive", "install"]),
+            (Manager::Zypper, Verb::Remove) => ("zypper", &["--non-interactive", "remove"]),
+        };
+
+        let mut invocation = Invocation::privileged(program, args);
+        invocation.args.extend(self.names.iter().cloned());
+        invocation
+    }
+}
+
+impl Helper {
+    /// Starts the helper as root, which prompts the user to authenticate.
+    pub fn spawn(elevation: Elevation) -> Result<Self, String> {
+        let executable = std::env::current_exe().map_err(|err| err.to_string())?;
+
+        let mut child = tokio::process::Command::new(elevation.program())
+            .arg(&executable)
+            .arg("helper")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            // Leaves stderr alone for sudo to ask for the password on the terminal.
+            .stderr(Stdio::inherit())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|err| format!("failed to run {}: {err}", elevation.program()))?;
+
+        let stdin = child.stdin.take().ok_or("the helper has no stdin")?;
+        let stdout = child.stdout.take().ok_or("the helper has no stdout")?;
+
+        Ok(Self {
+            child,
+            stdin,
+            stdout: BufReader::new(stdout).lines(),
+        })
+    }
+
+    /// Carries `request` out as root, passing each line the package manager prints
+    /// to `output`, and returns its exit code, if it has one.
+    pub async fn run(
+        &mut self,
+        request: &Request,
+        mut output: impl FnMut(String),
+    ) -> Result<Option<i32>, String> {
+        let mut line = serde_json::to_string(request).map_err(|err| err.to_string())?;
+        line.push('\n');
+
+        if self.stdin.write_all(line.as_bytes()).await.is_err() {
+            return Err(self.closed().await);
+        }
+
+        loop {
+            let line = match self.stdout.next_line().await {
+                Ok(Some(line)) => line,
+                Ok(None) | Err(_) => return Err(self.closed().await),
+            };
+
+            // Anything else the helper prints is passed along as output.
+            match serde_json::from_str(&line) {
+                Ok(Response::Output(line)) => output(line),
+                Ok(Response::Exited(code)) => return Ok(code),
+                Ok(Response::Error(err)) => return Err(err),
+                Err(_) => output(line),
+            }
+        }
+    }
+
+    /// Whether the helper exited, after which it cannot run anything else.
+    pub fn is_closed(&mut self) -> bool {
+        !matches!(self.child.try_wait(), Ok(None))
+    }
+
+    /// Why the helper stopped answering.
+    async fn closed(&mut self) -> String {
+        match self.child.wait().await {
+            // pkexec exits with 126 when the user dismisses the dialog and 127 when
+            // they are not authorized.
+            Ok(status) if matches!(status.code(), Some(126 | 127)) => {
+                "authentication was cancelled or failed".to_string()
+            }
+            Ok(status) => format!("the privileged helper exited with {status}"),
+            Err(err) => format!("the privileged helper stopped: {err}"),
+        }
+    }
+}
+
+/// Runs the helper side: reads requests from stdin until it closes, carrying out
+/// each valid one and answering on stdout.
+pub fn serve() -> ExitCode {
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(err) => {
+            eprintln!("error: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    runtime.block_on(async {
+        let mut requests = BufReader::new(tokio::io::stdin()).lines();
+
+        while let Ok(Some(request)) = requests.next_line().await {
+            let response = match serde_json::from_str::<Request>(&request) {
+                Ok(request) => run(&request).await,
+                Err(err) => Response::Error(format!("invalid request: {err}")),
+            };
+            respond(&response);
+        }
+    });
+
+    ExitCode::SUCCESS
+}
+
+async fn run(request: &Request) -> Response {
+    if let Err(err) = request.validate() {
+        return Response::Error(format!("invalid request: {err}"));
+    }
+
+    let invocation = request.invocation();
+    let mut child = match invocation.command().spawn() {
+        Ok(child) => child,
+        Err(err) => return Response::Error(format!("failed to run {}: {err}", invocation.program)),
+    };
+
+    let stdout = child.stdout.take().map(forward);
+    let stderr = child.stderr.take().map(forward);
+    let (status, _, _) = tokio::join!(
+        child.wait(),
+        async {
+            if let Some(stdout) = stdout {
+                stdout.await
+            }
+        },
+        async {
+            if let Some(stderr) = stderr {
+                stderr.await
+            }
+        }
+    );
+
+    match status {
+        Ok(status) => Response::Exited(status.code()),
+        Err(err) => Response::Error(err.to_string()),
+    }
+}
+
+async fn forward(output: impl AsyncRead + Unpin) {
+    let mut lines = BufReader::new(output).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        respond(&Response::Output(line));
+    }
+}
+
+fn respond(response: &Response) {
+    match serde_json::to_string(response) {
+        Ok(line) => println!("{line}"),
+        Err(err) => eprintln!("error: {err}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(names: &[&str]) -> Request {
+        Request {
+            verb: Verb::Install,
+            manager: Manager::Apt,
+            names: names.iter().map(|name| name.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn builds_the_call_itself() {
+        let request: Request =
+            serde_json::from_str(r#"{"verb":"remove","manager":"pacman","names":["fish"]}"#)
+                .unwrap();
+        assert_eq!(request.validate(), Ok(()));
+        assert_eq!(
+            request.invocation(),
+            Invocation::privileged("pacman", &["-R", "--noconfirm", "fish"])
+        );
+        assert_eq!(
+            Request::new(Verb::Install, &Source::Zypper("fish".into()))
+                .unwrap()
+                .invocation(),
+            Invocation::privileged("zypper", &["--non-interactive", "install", "fish"])
+        );
+    }
+
+    #[test]
+    fn refuses_other_programs() {
+        for line in [
+            r#"{"verb":"install","manager":"cargo","names":["fish"]}"#,
+            r#"{"verb":"run","manager":"apt","names":["fish"]}"#,
+            r#"{"program":"sh","args":["-c","id"],"privileged":true}"#,
+        ] {
+            assert!(serde_json::from_str::<Request>(line).is_err(), "{line}");
+        }
+        assert_eq!(
+            Request::new(
+                Verb::Install,
+                &Source::Go {
+                    package: "example.com/tool".into(),
+                    version: String::new(),
+                }
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn refuses_names_that_are_not_package_names() {
+        assert!(request(&["fish", "git"]).validate().is_ok());
+        for names in [
+            &[][..],
+            &[""],
+            &["-o", "APT::Get::Allow-Remove-Essential=true"],
+            &["fish", "--reinstall"],
+            &["./evil.deb"],
+            &["/tmp/evil.rpm"],
+            &["fish git"],
+            &["fish\n"],
+        ] {
+            assert!(request(names).validate().is_err(), "{names:?}");
+        }
+    }
+}
@@ -0,0 +1,377 @@
+// SPDX-License-Identifier: GPL-3.0
+
+//! Identifies the distribution a blueprint is applied on and its package managers.
+
+use std::path::{Path, PathBuf};
+
+use crate::app::models::package::{Kind, Package, Source};
+
+use super::detect::cargo_home;
+
+/// Directories, relative to the root, searched for package manager executables.
+const BIN_DIRS: [&str; 5] = ["usr/bin", "bin", "usr/sbin", "sbin", "usr/local/bin"];
+
+/// What Blueprint knows about the machine it applies blueprints on.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostInfo {
+    pub os: OsRelease,
+    /// The package managers with an executable on the machine.
+    pub managers: Vec<Kind>,
+    /// Whether curl is installed, to download the rustup installer with.
+    pub curl: bool,
+}
+
+/// The fields of os-release(5) used to pick the source of a package.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OsRelease {
+    /// A lowercase identifier such as `fedora`, `ubuntu` or `arch`.
+    pub id: String,
+    /// Identifiers of the distributions this one is derived from, closest first.
+    pub id_like: Vec<String>,
+    /// The release, such as `24.04`. Empty on rolling releases.
+    pub version_id: String,
+    pub pretty_name: String,
+}
+
+impl HostInfo {
+    /// Inspects the system mounted at `root`.
+    ///
+    /// Only files below `root` are read, so a fixture directory can stand in for
+    /// the live system. The directories users install tools like cargo to are only
+    /// searched when `root` is `/`.
+    pub fn detect(root: &Path) -> Self {
+        let mut dirs: Vec<PathBuf> = BIN_DIRS.iter().map(|dir| root.join(dir)).collect();
+        if root == Path::new("/") {
+            dirs.extend(user_bin_dirs());
+        }
+
+        let managers = Kind::ALL
+            .into_iter()
+            .filter(|kind| dirs.iter().any(|dir| dir.join(kind.program()).is_file()))
+            .collect();
+
+        Self {
+            os: OsRelease::load(root),
+            managers,
+            curl: dirs.iter().any(|dir| dir.join("curl").is_file()),
+        }
+    }
+
+    /// Whether the package manager of `source` is available. A missing rustup counts
+    /// as available if it can be installed on the way, from the distribution or
+    /// with its official installer.
+    pub fn supports(&self, source: &Source) -> bool {
+        match source.kind() {
+            Kind::Rustup => {
+                self.managers.contains(&Kind::Rustup)
+                    || self.rustup_package().is_some()
+                    || self.curl
+            }
+            kind => self.managers.contains(&kind),
+        }
+    }
+
+    /// The package of the distribution that installs rustup, if it has one and its
+    /// package manager is available.
+    pub fn rustup_package(&self) -> Option<Source> {
+        let os = &self.os;
+        // Releases of derivatives do not match Debian's or Ubuntu's, and Debian
+        // testing and unstable have none.
+        let kind = match os.id.as_str() {
+            "debian" if os.version_id.is_empty() || os.is_at_least(13, 0) => Kind::Apt,
+            "ubuntu" if os.is_at_least(24, 4) => Kind::Apt,
+            _ => os.ids().find_map(|id| match id {
+                "arch" => Some(Kind::Pacman),
+                "suse" | "opensuse" => Some(Kind::Zypper),
+                // Fedora only packages rustup-init, which downloads rustup all the
+                // same.
+                _ => None,
+            })?,
+        };
+
+        self.managers.contains(&kind).then(|| kind.source("rustup"))
+    }
+
+    /// The source to install `package` from: the one picked for the distribution if
+    /// its package manager is available, otherwise the first source that can be used.
+    pub fn source_for<'a>(&self, package: &'a Package) -> &'a Source {
+        let source = package.source_for(&self.os);
+        if self.supports(source) {
+            return source;
+        }
+
+        std::iter::once(&package.source)
+            .chain(package.alternatives.values())
+            .find(|source| self.supports(source))
+            .unwrap_or(source)
+    }
+
+    /// The package manager new packages should use: the native one of the
+    /// distribution, otherwise the first one available.
+    pub fn preferred_kind(&self) -> Option<Kind> {
+        let native = self.os.ids().find_map(|id| match id {
+            "debian" | "ubuntu" => Some(Kind::Apt),
+            "fedora" | "rhel" | "centos" => Some(Kind::Dnf),
+            "arch" => Some(Kind::Pacman),
+            "suse" | "opensuse" => Some(Kind::Zypper),
+            _ => None,
+        });
+
+        native
+            .filter(|kind| self.managers.contains(kind))
+            .or_else(|| self.managers.first().copied())
+    }
+}
+
+impl OsRelease {
+    /// Reads the os-release file of the system mounted at `root`.
+    pub fn load(root: &Path) -> Self {
+        ["etc/os-release", "usr/lib/os-release"]
+            .iter()
+            .find_map(|path| std::fs::read_to_string(root.join(path)).ok())
+            .map(|contents| Self::parse(&contents))
+            .unwrap_or_default()
+    }
+
+    pub fn parse(contents: &str) -> Self {
+        let mut os = Self::default();
+
+        for line in contents.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = unquote(value.trim());
+
+            match key.trim() {
+                "ID" => os.id = value,
+                "ID_LIKE" => os.id_like = value.split_whitespace().map(String::from).collect(),
+                "VERSION_ID" => os.version_id = value,
+                "PRETTY_NAME" => os.pretty_name = value,
+                _ => {}
+            }
+        }
+
+        os
+    }
+
+    /// Whether the release is at least `major.minor`.
+    fn is_at_least(&self, major: u32, minor: u32) -> bool {
+        let mut parts = self.version_id.split('.').map(str::parse::<u32>);
+        match (parts.next(), parts.next()) {
+            (Some(Ok(release)), None) => (release, 0) >= (major, minor),
+            (Some(Ok(release)), Some(Ok(point))) => (release, point) >= (major, minor),
+            _ => false,
+        }
+    }
+
+    /// The identifiers to look alternatives up by, most specific first.
+    pub fn ids(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.id.as_str())
+            .chain(self.id_like.iter().map(String::as_str))
+            .filter(|id| !id.is_empty())
+    }
+}
+
+/// The directories of the current user that package managers like cargo install
+/// themselves and their binaries to.
+pub fn user_bin_dirs() -> Vec<PathBuf> {
+    let home = dirs::home_dir();
+    [
+        cargo_home().map(|cargo| cargo.join("bin")),
+        home.map(|home| home.join(".local/bin")),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// Removes the shell quoting os-release values may use.
+fn unquote(value: &str) -> String {
+    let quoted = ['"', '\'']
+        .iter()
+        .find_map(|quote| value.strip_prefix(*quote)?.strip_suffix(*quote));
+
+    match quoted {
+        Some(inner) => {
+            let mut unquoted = String::with_capacity(inner.len());
+            let mut chars = inner.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => unquoted.extend(chars.next()),
+                    c => unquoted.push(c),
+                }
+            }
+            unquoted
+        }
+        None => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::models::package::Package;
+    use crate::app::Page;
+
+    use super::*;
+
+    /// A fake root with `os_release` and an executable for each of `programs`.
+    fn fixture(os_release: Option<&str>, programs: &[&str]) -> tempfile::TempDir {
+        let root = tempfile::tempdir().unwrap();
+        if let Some(os_release) = os_release {
+            std::fs::create_dir_all(root.path().join("etc")).unwrap();
+            std::fs::write(root.path().join("etc/os-release"), os_release).unwrap();
+        }
+        std::fs::create_dir_all(root.path().join("usr/bin")).unwrap();
+        for program in programs {
+            std::fs::write(root.path().join("usr/bin").join(program), "").unwrap();
+        }
+        root
+    }
+
+    fn detect(os_release: &str, programs: &[&str]) -> HostInfo {
+        HostInfo::detect(fixture(Some(os_release), programs).path())
+    }
+
+    fn fish() -> Package {
+        let mut package = Package::new("fish", Source::Apt("fish".into()), Page::Shells);
+        package
+            .alternatives
+            .insert("fedora".into(), Source::Dnf("fish".into()));
+        package
+            .alternatives
+            .insert("arch".into(), Source::Pacman("fish".into()));
+        package
+            .alternatives
+            .insert("suse".into(), Source::Zypper("fish".into()));
+        package
+    }
+
+    #[test]
+    fn debian() {
+        let host = detect(
+            "PRETTY_NAME=\"Debian GNU/Linux 12 (bookworm)\"\nID=debian\n",
+            &["apt-get", "flatpak"],
+        );
+        assert_eq!(host.os.id, "debian");
+        assert_eq!(host.os.pretty_name, "Debian GNU/Linux 12 (bookworm)");
+        assert_eq!(host.managers, [Kind::Apt, Kind::Flatpak]);
+        assert_eq!(host.preferred_kind(), Some(Kind::Apt));
+        assert_eq!(host.source_for(&fish()), &Source::Apt("fish".into()));
+    }
+
+    #[test]
+    fn fedora() {
+        let host = detect(
+            "NAME=\"Fedora Linux\"\nID=fedora\nVERSION_ID=40\n",
+            &["dnf"],
+        );
+        assert_eq!(host.os.ids().collect::<Vec<_>>(), ["fedora"]);
+        assert_eq!(host.preferred_kind(), Some(Kind::Dnf));
+        assert_eq!(host.source_for(&fish()), &Source::Dnf("fish".into()));
+    }
+
+    #[test]
+    fn arch() {
+        let host = detect("NAME=\"Arch Linux\"\nID=arch\n", &["pacman"]);
+        assert_eq!(host.preferred_kind(), Some(Kind::Pacman));
+        assert_eq!(host.source_for(&fish()), &Source::Pacman("fish".into()));
+    }
+
+    #[test]
+    fn opensuse_falls_back_to_id_like() {
+        let host = detect(
+            "ID=\"opensuse-tumbleweed\"\nID_LIKE=\"opensuse suse\"\n",
+            &["zypper"],
+        );
+        assert_eq!(
+            host.os.ids().collect::<Vec<_>>(),
+            ["opensuse-tumbleweed", "opensuse", "suse"]
+        );
+        assert_eq!(host.preferred_kind(), Some(Kind::Zypper));
+        assert_eq!(host.source_for(&fish()), &Source::Zypper("fish".into()));
+    }
+
+    #[test]
+    fn id_like_picks_the_closest_alternative() {
+        let host = detect("ID=pop\nID_LIKE=\"ubuntu debian\"\n", &["apt-get"]);
+        let mut package = fish();
+        package
+            .alternatives
+            .insert("ubuntu".into(), Source::Apt("fish-ubuntu".into()));
+        assert_eq!(
+            host.source_for(&package),
+            &Source::Apt("fish-ubuntu".into())
+        );
+        assert_eq!(host.preferred_kind(), Some(Kind::Apt));
+    }
+
+    #[test]
+    fn source_for_skips_unavailable_managers() {
+        // An Ubuntu machine with only Flatpak picks the Flatpak alternative.
+        let host = detect("ID=ubuntu\nID_LIKE=debian\n", &["flatpak"]);
+        let mut package = fish();
+        package.alternatives.insert(
+            "flathub".into(),
+            Source::Flatpak {
+                id: "io.fish".into(),
+                version: String::new(),
+            },
+        );
+        assert!(matches!(host.source_for(&package), Source::Flatpak { .. }));
+        assert_eq!(host.preferred_kind(), Some(Kind::Flatpak));
+    }
+
+    #[test]
+    fn quoted_values() {
+        let os = OsRelease::parse(
+            "ID='debian'\nPRETTY_NAME=\"Say \\\"hi\\\" \\\\ bye\"\nID_LIKE=\n# comment\n",
+        );
+        assert_eq!(os.id, "debian");
+        assert_eq!(os.pretty_name, "Say \"hi\" \\ bye");
+        assert!(os.id_like.is_empty());
+    }
+
+    #[test]
+    fn missing_os_release() {
+        let root = fixture(None, &[]);
+        let host = HostInfo::detect(root.path());
+        assert_eq!(host.os, OsRelease::default());
+        assert_eq!(host.os.ids().count(), 0);
+        assert!(host.managers.is_empty());
+        assert_eq!(host.preferred_kind(), None);
+    }
+
+    #[test]
+    fn falls_back_to_usr_lib_os_release() {
+        let root = fixture(None, &[]);
+        std::fs::create_dir_all(root.path().join("usr/lib")).unwrap();
+        std::fs::write(root.path().join("usr/lib/os-release"), "ID=arch\n").unwrap();
+        assert_eq!(HostInfo::detect(root.path()).os.id, "arch");
+    }
+
+    #[test]
+    fn rustup_is_installed_from_the_distribution_when_it_has_it() {
+        let toolchain = Kind::Rustup.source("stable");
+
+        let arch = detect("ID=arch\n", &["pacman"]);
+        assert_eq!(arch.rustup_package(), Some(Source::Pacman("rustup".into())));
+        assert!(arch.supports(&toolchain));
+
+        let noble = detect("ID=ubuntu\nVERSION_ID=\"24.04\"\n", &["apt-get"]);
+        assert_eq!(noble.rustup_package(), Some(Source::Apt("rustup".into())));
+        let jammy = detect("ID=ubuntu\nVERSION_ID=\"22.04\"\n", &["apt-get"]);
+        assert_eq!(jammy.rustup_package(), None);
+        let trixie = detect("ID=debian\nVERSION_ID=\"13\"\n", &["apt-get"]);
+        assert_eq!(trixie.rustup_package(), Some(Source::Apt("rustup".into())));
+        let bookworm = detect("ID=debian\nVERSION_ID=\"12\"\n", &["apt-get"]);
+        assert_eq!(bookworm.rustup_package(), None);
+
+        // Without rustup, its package or curl, toolchains cannot be installed.
+        assert!(!bookworm.supports(&toolchain));
+        let bookworm = detect("ID=debian\nVERSION_ID=\"12\"\n", &["apt-get", "curl"]);
+        assert!(bookworm.supports(&toolchain));
+        let fedora = detect("ID=fedora\nVERSION_ID=40\n", &["dnf", "rustup"]);
+        assert_eq!(fedora.rustup_package(), None);
+        assert!(fedora.supports(&toolchain));
+    }
+}
@@ -0,0 +1,636 @@
+// SPDX-License-Identifier: GPL-3.0
+
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use serde::{Deserialize, Serialize};
+
+use crate::app::models::package::{requirement, specifier, Source};
+
+use super::detect::{go_bin_dir, go_binary};
+use super::helper::{Request, Verb};
+
+/// Installs rustup for the user with its official installer unless it is already
+/// there, without a toolchain and without editing the shell profile.
+const RUSTUP_BOOTSTRAP: &str = "command -v rustup >/dev/null 2>&1 || \
+    curl --proto '=https' --tlsv1.2 -sSf https://sh.rustup.rs \
+    | sh -s -- -y --no-modify-path --default-toolchain none";
+
+/// A single package manager call needed to install a package.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Invocation {
+    pub program: String,
+    pub args: Vec<String>,
+    /// Whether the call needs to run as root.
+    pub privileged: bool,
+}
+
+impl Invocation {
+    pub fn new(program: &str, args: &[&str]) -> Self {
+        Self {
+            program: program.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            privileged: false,
+        }
+    }
+
+    pub fn privileged(program: &str, args: &[&str]) -> Self {
+        Self {
+            privileged: true,
+            ..Self::new(program, args)
+        }
+    }
+
+    /// Builds the process for this invocation, run as the current user. Privileged
+    /// invocations go through the [helper](super::helper) unless we are root.
+    pub fn command(&self) -> tokio::process::Command {
+        let mut command = tokio::process::Command::new(&self.program);
+
+        // Finds tools installed for the user, such as cargo, even when the
+        // application was not started from a shell that adds them to the path.
+        if !self.privileged {
+            let path = std::env::var_os("PATH").unwrap_or_default();
+            let dirs = super::host::user_bin_dirs()
+                .into_iter()
+                .chain(std::env::split_paths(&path));
+            if let Ok(path) = std::env::join_paths(dirs) {
+                command.env("PATH", path);
+            }
+        }
+
+        // Keeps apt from stopping to ask questions no one can answer, whether it runs
+        // in the helper or as root.
+        if self.program == "apt-get" {
+            command.env("DEBIAN_FRONTEND", "noninteractive");
+        }
+
+        command
+            .args(&self.args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        command
+    }
+}
+
+impl Display for Invocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.program)?;
+        for arg in &self.args {
+            write!(f, " {arg}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Source {
+    /// The package manager calls that install this source, in order.
+    pub fn install(&self) -> Vec<Invocation> {
+        match self {
+            // Built the way the helper builds them, so what is shown is what runs.
+            Source::Apt(_) | Source::Dnf(_) | Source::Pacman(_) | Source::Zypper(_) => {
+                Request::new(Verb::Install, self)
+                    .iter()
+                    .map(Request::invocation)
+                    .collect()
+            }
+            Source::Flatpak { id, version } => {
+                let reference = if version.is_empty() {
+                    id.clone()
+                } else {
+                    format!("{id}//{version}")
+                };
+                vec![
+                    Invocation::new(
+                        "flatpak",
+                        &[
+                            "remote-add",
+                            "--user",
+                            "--if-not-exists",
+                            "flathub",
+                            "https://dl.flathub.org/repo/flathub.flatpakrepo",
+                        ],
+                    ),
+                    Invocation::new(
+                        "flatpak",
+                        &[
+                            "install",
+                            "--user",
+                            "-y",
+                            "--noninteractive",
+                            "flathub",
+                            &reference,
+                        ],
+                    ),
+                ]
+            }
+            Source::Cargo {
+                name,
+                version,
+                features,
+                locked,
+                git,
+            } => {
+                let mut args = vec!["install"];
+                if !git.is_empty() {
+                    args.extend(["--git", git]);
+                }
+                if !version.is_empty() {
+                    args.extend(["--version", version]);
+                }
+                let features = features.join(",");
+                if !features.is_empty() {
+                    args.extend(["--features", &features]);
+                }
+                if *locked {
+                    args.push("--locked");
+                }
+                args.push(name);
+                vec![Invocation::new("cargo", &args)]
+            }
+            Source::Rustup {
+                toolchain,
+                targets,
+                components,
+            } => {
+                // Adds the targets and components that are missing when the
+                // toolchain is already installed.
+                let mut args = vec!["toolchain", "install", toolchain];
+                for target in targets {
+                    args.extend(["--target", target]);
+                }
+                for component in components {
+                    args.extend(["--component", component]);
+                }
+                vec![Invocation::new("rustup", &args)]
+            }
+            // `--force` replaces an installed version that does not match.
+            Source::Pipx {
+                name,
+                version,
+                extras,
+            } => {
+                let mut invocations = vec![Invocation::new(
+                    "pipx",
+                    &["install", "--force", &requirement(name, version)],
+                )];
+                if !extras.is_empty() {
+                    // Extras are validated, but `--` also keeps pipx from reading one as an
+                    // option.
+                    let mut args = vec!["inject", name.as_str(), "--"];
+                    args.extend(extras.iter().map(String::as_str));
+                    invocations.push(Invocation::new("pipx", &args));
+                }
+                invocations
+            }
+            Source::Uv {
+                name,
+                version,
+                extras,
+            } => {
+                let requirement = requirement(name, version);
+                let mut args = vec!["tool", "install", "--force", requirement.as_str()];
+                for extra in extras {
+                    args.extend(["--with", extra]);
+                }
+                vec![Invocation::new("uv", &args)]
+            }
+            Source::Npm { name, version } => vec![Invocation::new(
+                "npm",
+                &["install", "--global", &specifier(name, version)],
+            )],
+            Source::Pnpm { name, version } => vec![Invocation::new(
+                "pnpm",
+                &["add", "--global", &specifier(name, version)],
+            )],
+            Source::Go { package, version } => {
+                let version = if version.is_empty() {
+                    "latest"
+                } else {
+                    version
+                };
+                vec![Invocation::new(
+                    "go",
+                    &["install", &format!("{package}@{version}")],
+                )]
+            }
+        }
+    }
+
+    /// The package manager calls that remove this source, in order.
+    pub fn remove(&self) -> Vec<Invocation> {
+        match self {
+            Source::Apt(_) | Source::Dnf(_) | Source::Pacman(_) | Source::Zypper(_) => {
+                Request::new(Verb::Remove, self)
+                    .iter()
+                    .map(Request::invocation)
+                    .collect()
+            }
+            Source::Flatpak { id, .. } => vec![Invocation::new(
+                "flatpak",
+                &["uninstall", "--user", "-y", "--noninteractive", id],
+            )],
+            Source::Cargo { name, .. } => vec![Invocation::new("cargo", &["uninstall", name])],
+            Source::Rustup { toolchain, .. } => {
+                vec![Invocation::new(
+                    "rustup",
+                    &["toolchain", "uninstall", toolchain],
+                )]
+            }
+            Source::Pipx { name, .. } => vec![Invocation::new("pipx", &["uninstall", name])],
+            Source::Uv { name, .. } => vec![Invocation::new("uv", &["tool", "uninstall", name])],
+            Source::Npm { name, .. } => {
+                vec![Invocation::new("npm", &["uninstall", "--global", name])]
+            }
+            Source::Pnpm { name, .. } => {
+                vec![Invocation::new("pnpm", &["remove", "--global", name])]
+            }
+            // Go has no uninstall; the engine deletes the binary with `remove_go_binary`.
+            Source::Go { .. } => vec![],
+        }
+    }
+}
+
+/// The call that installs rustup with its official installer, for machines whose
+/// distribution does not package it. The plan asks before making it.
+pub fn bootstrap_rustup() -> Invocation {
+    Invocation::new("sh", &["-c", RUSTUP_BOOTSTRAP])
+}
+
+/// Deletes the binary `go install` made for `package`, since Go has no uninstall,
+/// and returns its path. A binary that is already gone counts as removed.
+pub fn remove_go_binary(package: &str) -> Result<PathBuf, String> {
+    let dir = go_bin_dir().ok_or("cannot tell where Go installs binaries; set GOBIN or GOPATH")?;
+    remove_binary(&dir, package)
+}
+
+/// Deletes the binary of the Go `package` from `dir`.
+fn remove_binary(dir: &Path, package: &str) -> Result<PathBuf, String> {
+    let binary = dir.join(go_binary(package));
+
+    match std::fs::remove_file(&binary) {
+        Ok(()) => Ok(binary),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(binary),
+        Err(err) => Err(format!("failed to remove {}: {err}", binary.display())),
+    }
+}
+
+pub fn is_root() -> bool {
+    // SAFETY: `geteuid` has no preconditions and cannot fail.
+    unsafe { libc::geteuid() == 0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::models::package::{Invalid, Package};
+    use crate::app::Page;
+
+    use super::*;
+
+    #[test]
+    fn sources_are_installed_and_removed_with_their_package_manager() {
+        let strings = |list: &[&str]| list.iter().map(|item| item.to_string()).collect();
+        let cases = [
+            (
+                Source::Apt("fish".into()),
+                vec![Invocation::privileged(
+                    "apt-get",
+                    &["install", "-y", "fish"],
+                )],
+                vec![Invocation::privileged("apt-get", &["remove", "-y", "fish"])],
+            ),
+            (
+                Source::Dnf("fish".into()),
+                vec![Invocation::privileged("dnf", &["install", "-y", "fish"])],
+                vec![Invocation::privileged("dnf", &["remove", "-y", "fish"])],
+            ),
+            (
+                Source::Pacman("fish".into()),
+                vec![Invocation::privileged(
+                    "pacman",
+                    &["-S", "--needed", "--noconfirm", "fish"],
+                )],
+                vec![Invocation::privileged(
+                    "pacman",
+                    &["-R", "--noconfirm", "fish"],
+                )],
+            ),
+            (
+                Source::Zypper("fish".into()),
+                vec![Invocation::privileged(
+                    "zypper",
+                    &["--non-interactive", "install", "fish"],
+                )],
+                vec![Invocation::privileged(
+                    "zypper",
+                    &["--non-interactive", "remove", "fish"],
+                )],
+            ),
+            (
+                Source::Flatpak {
+                    id: "org.gimp.GIMP".into(),
+                    version: "beta".into(),
+                },
+                vec![
+                    Invocation::new(
+                        "flatpak",
+                        &[
+                            "remote-add",
+                            "--user",
+                            "--if-not-exists",
+                            "flathub",
+                            "https://dl.flathub.org/repo/flathub.flatpakrepo",
+                        ],
+                    ),
+                    Invocation::new(
+                        "flatpak",
+                        &[
+                            "install",
+                            "--user",
+                            "-y",
+                            "--noninteractive",
+                            "flathub",
+                            "org.gimp.GIMP//beta",
+                        ],
+                    ),
+                ],
+                vec![Invocation::new(
+                    "flatpak",
+                    &[
+                        "uninstall",
+                        "--user",
+                        "-y",
+                        "--noninteractive",
+                        "org.gimp.GIMP",
+                    ],
+                )],
+            ),
+            (
+                Source::Cargo {
+                    name: "ripgrep".into(),
+                    version: "14.1.0".into(),
+                    features: strings(&["pcre2", "simd"]),
+                    locked: true,
+                    git: "https://github.com/BurntSushi/ripgrep".into(),
+                },
+                vec![Invocation::new(
+                    "cargo",
+                    &[
+                        "install",
+                        "--git",
+                        "https://github.com/BurntSushi/ripgrep",
+                        "--version",
+                        "14.1.0",
+                        "--features",
+                        "pcre2,simd",
+                        "--locked",
+                        "ripgrep",
+                    ],
+                )],
+                vec![Invocation::new("cargo", &["uninstall", "ripgrep"])],
+            ),
+            (
+                Source::Rustup {
+                    toolchain: "stable".into(),
+                    targets: strings(&["wasm32-unknown-unknown"]),
+                    components: strings(&["rust-src"]),
+                },
+                vec![Invocation::new(
+                    "rustup",
+                    &[
+                        "toolchain",
+                        "install",
+                        "stable",
+                        "--target",
+                        "wasm32-unknown-unknown",
+                        "--component",
+                        "rust-src",
+                    ],
+                )],
+                vec![Invocation::new(
+                    "rustup",
+                    &["toolchain", "uninstall", "stable"],
+                )],
+            ),
+            (
+                Source::Pipx {
+                    name: "black".into(),
+                    version: ">=24".into(),
+                    extras: vec![],
+                },
+                vec![Invocation::new(
+                    "pipx",
+                    &["install", "--force", "black>=24"],
+                )],
+                vec![Invocation::new("pipx", &["uninstall", "black"])],
+            ),
+            (
+                Source::Uv {
+                    name: "ruff".into(),
+                    version: "0.4.4".into(),
+                    extras: strings(&["pyyaml"]),
+                },
+                vec![Invocation::new(
+                    "uv",
+                    &[
+                        "tool",
+                        "install",
+                        "--force",
+                        "ruff==0.4.4",
+                        "--with",
+                        "pyyaml",
+                    ],
+                )],
+                vec![Invocation::new("uv", &["tool", "uninstall", "ruff"])],
+            ),
+            (
+                Source::Npm {
+                    name: "typescript".into(),
+                    version: "^5".into(),
+                },
+                vec![Invocation::new(
+                    "npm",
+                    &["install", "--global", "typescript@^5"],
+                )],
+                vec![Invocation::new(
+                    "npm",
+                    &["uninstall", "--global", "typescript"],
+                )],
+            ),
+            (
+                Source::Pnpm {
+                    name: "typescript".into(),
+                    version: String::new(),
+                },
+                vec![Invocation::new("pnpm", &["add", "--global", "typescript"])],
+                vec![Invocation::new(
+                    "pnpm",
+                    &["remove", "--global", "typescript"],
+                )],
+            ),
+            (
+                Source::Go {
+                    package: "golang.org/x/tools/gopls".into(),
+                    version: String::new(),
+                },
+                vec![Invocation::new(
+                    "go",
+                    &["install", "golang.org/x/tools/gopls@latest"],
+                )],
+                // The engine deletes the binary itself.
+                vec![],
+            ),
+        ];
+
+        for (source, install, remove) in cases {
+            assert_eq!(source.install(), install, "{source}");
+            assert_eq!(source.remove(), remove, "{source}");
+        }
+    }
+
+    #[test]
+    fn go_binaries_are_deleted() {
+        let dir = tempfile::tempdir().unwrap();
+        let binary = dir.path().join("gopls");
+        std::fs::write(&binary, "").unwrap();
+
+        assert_eq!(
+            remove_binary(dir.path(), "golang.org/x/tools/gopls"),
+            Ok(binary.clone())
+        );
+        assert!(!binary.exists());
+
+        // A binary that is already gone counts as removed.
+        assert_eq!(
+            remove_binary(dir.path(), "golang.org/x/tools/gopls"),
+            Ok(binary)
+        );
+
+        // Something that cannot be deleted as a file is an error.
+        std::fs::create_dir(dir.path().join("staticcheck")).unwrap();
+        assert!(remove_binary(dir.path(), "honnef.co/go/tools/cmd/staticcheck").is_err());
+    }
+
+    #[test]
+    fn pipx_extras_are_injected_as_arguments() {
+        let source = Source::Pipx {
+            name: "black".into(),
+            version: String::new(),
+            extras: vec!["black[d]".into()],
+        };
+
+        assert_eq!(
+            source.install(),
+            [
+                Invocation::new("pipx", &["install", "--force", "black"]),
+                Invocation::new("pipx", &["inject", "black", "--", "black[d]"]),
+            ]
+        );
+
+        for extra in ["", "--index-url=https://example.com", "black d"] {
+            let package = Package::new(
+                "Black",
+                Source::Pipx {
+                    name: "black".into(),
+                    version: String::new(),
+                    extras: vec![extra.into()],
+                },
+                Page::Tools,
+            );
+            assert_eq!(package.validate(), [Invalid::Extras], "{extra:?}");
+        }
+    }
+
+    #[test]
+    fn cargo_options_are_validated() {
+        let cargo = |version: &str, features: &[&str], git: &str| {
+            let source = Source::Cargo {
+                name: "ripgrep".into(),
+                version: version.into(),
+                features: features.iter().map(|feature| feature.to_string()).collect(),
+                locked: false,
+                git: git.into(),
+            };
+            Package::new("ripgrep", source, Page::Tools).validate()
+        };
+
+        assert_eq!(cargo("", &[], ""), []);
+        assert_eq!(
+            cargo(
+                "14.1.0",
+                &["pcre2"],
+                "https://github.com/BurntSushi/ripgrep"
+            ),
+            []
+        );
+        assert_eq!(cargo("--force", &[], ""), [Invalid::Version]);
+        assert_eq!(cargo("", &[""], ""), [Invalid::Features]);
+        assert_eq!(cargo("", &["--all-features"], ""), [Invalid::Features]);
+        assert_eq!(cargo("", &[], "--path=/tmp"), [Invalid::Git]);
+        assert_eq!(cargo("", &[], "https://a b"), [Invalid::Git]);
+    }
+
+    #[test]
+    fn rustup_targets_and_components_are_validated() {
+        let rustup = |targets: &[&str], components: &[&str]| {
+            let source = Source::Rustup {
+                toolchain: "stable".into(),
+                targets: targets.iter().map(|target| target.to_string()).collect(),
+                components: components
+                    .iter()
+                    .map(|component| component.to_string())
+                    .collect(),
+            };
+            Package::new("Rust", source, Page::Languages).validate()
+        };
+
+        assert_eq!(rustup(&["wasm32-unknown-unknown"], &["rust-src"]), []);
+        assert_eq!(rustup(&[""], &[]), [Invalid::Targets]);
+        assert_eq!(rustup(&["--force"], &[]), [Invalid::Targets]);
+        assert_eq!(rustup(&[], &["rust src"]), [Invalid::Components]);
+        assert_eq!(rustup(&[], &["-y"]), [Invalid::Components]);
+    }
+
+    #[test]
+    fn node_versions_are_validated() {
+        for (version, errors) in [
+            ("", vec![]),
+            ("^5", vec![]),
+            ("next", vec![]),
+            ("--prefix=/", vec![Invalid::Version]),
+            ("5 || 6", vec![Invalid::Version]),
+        ] {
+            for source in [
+                Source::Npm {
+                    name: "typescript".into(),
+                    version: version.into(),
+                },
+                Source::Pnpm {
+                    name: "typescript".into(),
+                    version: version.into(),
+                },
+            ] {
+                let package = Package::new("TypeScript", source, Page::Languages);
+                assert_eq!(package.validate(), errors, "{version:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn go_versions_are_validated() {
+        let go = |version: &str| {
+            let source = Source::Go {
+                package: "golang.org/x/tools/gopls".into(),
+                version: version.into(),
+            };
+            Package::new("gopls", source, Page::Languages).validate()
+        };
+
+        assert_eq!(go(""), []);
+        assert_eq!(go("v0.15.3"), []);
+        assert_eq!(go("-v"), [Invalid::Version]);
+        assert_eq!(go("v0.15.3 -a"), [Invalid::Version]);
+    }
+}
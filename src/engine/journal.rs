@@ -0,0 +1,303 @@
+// SPDX-License-Identifier: GPL-3.0
+
+//! An append-only record of everything the engine did to a machine, kept under the
+//! XDG state directory for auditing and rolling runs back.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::app::models::package::{Package, Source};
+use crate::config::APP_ID;
+
+use super::deploy::Deployment;
+use super::revert::Applied;
+
+/// The journal file, with one JSON entry per line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Journal {
+    pub path: PathBuf,
+}
+
+/// A line of the journal.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Entry {
+    /// The run the entry belongs to.
+    pub run: Uuid,
+    /// Seconds since the Unix epoch.
+    pub time: u64,
+    #[serde(flatten)]
+    pub record: Record,
+}
+
+/// What happened at one point of a run.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Record {
+    Started,
+    /// A package manager call made for a package and the code it exited with, if it
+    /// ran to completion.
+    Command {
+        package: Uuid,
+        source: Source,
+        command: String,
+        status: Option<i32>,
+    },
+    Installed {
+        package: Uuid,
+        name: String,
+        source: Source,
+    },
+    Removed {
+        package: Uuid,
+        name: String,
+        source: Source,
+    },
+    Failed {
+        package: Uuid,
+        error: String,
+    },
+    /// A config file was written, after saving what it contained to `backup`.
+    Written {
+        package: Uuid,
+        target: PathBuf,
+        backup: Option<PathBuf>,
+    },
+    /// A config file was put back as it was before Blueprint wrote it.
+    Restored {
+        target: PathBuf,
+    },
+    Finished,
+}
+
+/// A past run, put together from its journal entries.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Transaction {
+    pub id: Uuid,
+    pub started: u64,
+    /// The entries of the run in the order they were recorded.
+    pub records: Vec<Record>,
+}
+
+impl Journal {
+    /// The journal in the state directory of the user.
+    pub fn open() -> Option<Self> {
+        Some(Self {
+            path: dirs::state_dir()?.join(APP_ID).join("journal.jsonl"),
+        })
+    }
+
+    /// Adds `record` of `run` at the end of the journal, timestamped now.
+    pub fn append(&self, run: Uuid, record: Record) -> io::Result<()> {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        let mut line = serde_json::to_string(&Entry { run, time, record })?;
+        line.push('\n');
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // The line is written at once, so entries of concurrent runs never mix.
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(line.as_bytes())
+    }
+
+    /// Every run in the journal, newest first. Lines that cannot be read are skipped.
+    pub fn load(&self) -> io::Result<Vec<Transaction>> {
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(err) => return Err(err),
+        };
+
+        let mut transactions: Vec<Transaction> = vec![];
+        for (number, line) in contents.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let entry: Entry = match serde_json::from_str(line) {
+                Ok(entry) => entry,
+                Err(err) => {
+                    log::error!("skipping line {} of the journal: {}", number + 1, err);
+                    continue;
+                }
+            };
+
+            match transactions.iter_mut().find(|run| run.id == entry.run) {
+                Some(transaction) => transaction.records.push(entry.record),
+                None => transactions.push(Transaction {
+                    id: entry.run,
+                    started: entry.time,
+                    records: vec![entry.record],
+                }),
+            }
+        }
+
+        transactions.reverse();
+        Ok(transactions)
+    }
+}
+
+impl Transaction {
+    pub fn started_at(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.started)
+    }
+
+    /// Whether the run got to its end, rather than being interrupted.
+    pub fn is_finished(&self) -> bool {
+        self.records.contains(&Record::Finished)
+    }
+
+    /// What the run changed on the machine, for rolling it back: the packages it
+    /// installed and the files it wrote.
+    pub fn applied(&self) -> Applied {
+        let mut applied = Applied::default();
+
+        for record in &self.records {
+            match record {
+                Record::Installed {
+                    package,
+                    name,
+                    source,
+                } => {
+                    let mut installed = Package::new(name, source.clone(), Default::default());
+                    installed.id = *package;
+                    applied.packages.push(installed);
+                }
+                Record::Written {
+                    package,
+                    target,
+                    backup,
+                } if !applied.files.iter().any(|file| file.target == *target) => {
+                    applied.files.push(Deployment {
+                        package: *package,
+                        target: target.clone(),
+                        backup: backup.clone(),
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        applied
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn written(package: Uuid, target: &str, backup: Option<&str>) -> Record {
+        Record::Written {
+            package,
+            target: target.into(),
+            backup: backup.map(PathBuf::from),
+        }
+    }
+
+    #[test]
+    fn runs_are_read_back_newest_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal = Journal {
+            path: dir.path().join("state/journal.jsonl"),
+        };
+        assert_eq!(journal.load().unwrap(), []);
+
+        let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+        let package = Uuid::new_v4();
+        journal.append(first, Record::Started).unwrap();
+        journal.append(second, Record::Started).unwrap();
+        journal
+            .append(first, written(package, "/home/user/.gitconfig", None))
+            .unwrap();
+        journal.append(first, Record::Finished).unwrap();
+
+        let runs = journal.load().unwrap();
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].id, second);
+        assert!(!runs[0].is_finished());
+        assert_eq!(runs[1].id, first);
+        assert_eq!(
+            runs[1].records,
+            [
+                Record::Started,
+                written(package, "/home/user/.gitconfig", None),
+                Record::Finished,
+            ]
+        );
+    }
+
+    #[test]
+    fn a_truncated_last_line_is_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal = Journal {
+            path: dir.path().join("journal.jsonl"),
+        };
+        let run = Uuid::new_v4();
+        journal.append(run, Record::Started).unwrap();
+
+        // As left by a crash in the middle of a write.
+        let mut file = OpenOptions::new().append(true).open(&journal.path).unwrap();
+        write!(file, "{{\"run\":\"{run}\",\"time\":1,\"event\":\"fin").unwrap();
+
+        let runs = journal.load().unwrap();
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].records, [Record::Started]);
+    }
+
+    #[test]
+    fn applied_has_what_the_run_installed_and_first_wrote() {
+        let (package, other) = (Uuid::new_v4(), Uuid::new_v4());
+        let transaction = Transaction {
+            id: Uuid::new_v4(),
+            started: 0,
+            records: vec![
+                Record::Started,
+                Record::Installed {
+                    package,
+                    name: "Fish".into(),
+                    source: Source::Apt("fish".into()),
+                },
+                Record::Failed {
+                    package: other,
+                    error: "exited with 100".into(),
+                },
+                written(
+                    package,
+                    "/home/user/.config/fish/config.fish",
+                    Some("/a.bak"),
+                ),
+                written(
+                    package,
+                    "/home/user/.config/fish/config.fish",
+                    Some("/b.bak"),
+                ),
+                Record::Finished,
+            ],
+        };
+
+        let applied = transaction.applied();
+        assert_eq!(applied.packages.len(), 1);
+        assert_eq!(applied.packages[0].id, package);
+        assert_eq!(applied.packages[0].source, Source::Apt("fish".into()));
+        assert_eq!(
+            applied.files,
+            [Deployment {
+                package,
+                target: "/home/user/.config/fish/config.fish".into(),
+                backup: Some("/a.bak".into()),
+            }]
+        );
+    }
+}
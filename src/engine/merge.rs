@@ -0,0 +1,445 @@
+// SPDX-License-Identifier: GPL-3.0
+
+//! Deep-merges partial JSON, TOML, INI and YAML documents into existing files, so
+//! settings the user added themselves are kept.
+
+use std::fmt::Display;
+
+/// The structured formats a config file can be merged in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// JSON, also accepting the comments and trailing commas editors allow.
+    Json,
+    Toml,
+    Ini,
+    Yaml,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The file on disk could not be parsed.
+    Existing(String),
+    /// The blueprint's partial document could not be parsed.
+    Partial(String),
+    /// The merged document could not be written out.
+    Serialize(String),
+}
+
+impl Format {
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.trim_start_matches('.').to_lowercase().as_str() {
+            "json" | "jsonc" => Some(Self::Json),
+            "toml" => Some(Self::Toml),
+            "ini" | "conf" => Some(Self::Ini),
+            "yaml" | "yml" => Some(Self::Yaml),
+            _ => None,
+        }
+    }
+}
+
+/// Merges `partial` into `existing`.
+///
+/// Tables are merged key by key, recursively; any other value in `partial`, arrays
+/// included, replaces the one in `existing`. An empty `existing` is treated as an
+/// empty document. TOML and INI files keep their comments and layout, JSON and YAML
+/// files are written out again and lose their comments.
+pub fn merge(existing: &str, partial: &str, format: Format) -> Result<String, Error> {
+    match format {
+        Format::Json => merge_json(existing, partial),
+        Format::Toml => merge_toml(existing, partial),
+        Format::Ini => merge_ini(existing, partial),
+        Format::Yaml => merge_yaml(existing, partial),
+    }
+}
+
+fn merge_json(existing: &str, partial: &str) -> Result<String, Error> {
+    let parse = |document: &str| -> Result<serde_json::Value, String> {
+        let document = strip_json_comments(document);
+        if document.trim().is_empty() {
+            return Ok(serde_json::Value::Object(Default::default()));
+        }
+        serde_json::from_str(&document).map_err(|err| err.to_string())
+    };
+
+    let mut merged = parse(existing).map_err(Error::Existing)?;
+    let partial = parse(partial).map_err(Error::Partial)?;
+    merge_json_value(&mut merged, partial);
+
+    // Keeps the indentation of the existing file, four spaces for VS Code and
+    // tabs for others.
+    let indent = existing
+        .lines()
+        .find_map(|line| {
+            let indent: String = line
+                .chars()
+                .take_while(|c| *c == ' ' || *c == '\t')
+                .collect();
+            (!indent.is_empty()).then_some(indent)
+        })
+        .unwrap_or_else(|| "  ".to_string());
+
+    let mut output = vec![];
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+    let mut serializer = serde_json::Serializer::with_formatter(&mut output, formatter);
+    serde::Serialize::serialize(&merged, &mut serializer)
+        .map_err(|err| Error::Serialize(err.to_string()))?;
+
+    let mut output = String::from_utf8(output).map_err(|err| Error::Serialize(err.to_string()))?;
+    output.push('\n');
+    Ok(output)
+}
+
+fn merge_json_value(existing: &mut serde_json::Value, partial: serde_json::Value) {
+    match (existing, partial) {
+        (serde_json::Value::Object(existing), serde_json::Value::Object(partial)) => {
+            for (key, value) in partial {
+                match existing.get_mut(&key) {
+                    Some(current) => merge_json_value(current, value),
+                    None => {
+                        existing.insert(key, value);
+                    }
+                }
+            }
+        }
+        (existing, partial) => *existing = partial,
+    }
+}
+
+/// Removes `//` and `/* */` comments and trailing commas outside of strings.
+fn strip_json_comments(document: &str) -> String {
+    let mut stripped = String::with_capacity(document.len());
+    let mut chars = document.chars().peekable();
+    let mut in_string = false;
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            stripped.push(c);
+            match c {
+                '\\' => stripped.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match (c, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                stripped.push(c);
+            }
+            ('/', Some('/')) => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        stripped.push(c);
+                        break;
+                    }
+                }
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut previous = None;
+                for c in chars.by_ref() {
+                    if previous == Some('*') && c == '/' {
+                        break;
+                    }
+                    previous = Some(c);
+                }
+            }
+            ('}' | ']', _) => {
+                // Drops the comma closing the last element, if any.
+                let end = stripped.trim_end().len();
+                if stripped[..end].ends_with(',') {
+                    stripped.remove(end - 1);
+                }
+                stripped.push(c);
+            }
+            _ => stripped.push(c),
+        }
+    }
+
+    stripped
+}
+
+fn merge_toml(existing: &str, partial: &str) -> Result<String, Error> {
+    let mut merged: toml_edit::DocumentMut = existing
+        .parse()
+        .map_err(|err: toml_edit::TomlError| Error::Existing(err.to_string()))?;
+    let partial: toml_edit::DocumentMut = partial
+        .parse()
+        .map_err(|err: toml_edit::TomlError| Error::Partial(err.to_string()))?;
+
+    merge_toml_item(merged.as_item_mut(), partial.as_item());
+    Ok(merged.to_string())
+}
+
+fn merge_toml_item(existing: &mut toml_edit::Item, partial: &toml_edit::Item) {
+    match (existing.as_table_like_mut(), partial.as_table_like()) {
+        (Some(existing), Some(partial)) => {
+            for (key, item) in partial.iter() {
+                match existing.get_mut(key) {
+                    Some(current) => merge_toml_item(current, item),
+                    None => {
+                        existing.insert(key, item.clone());
+                    }
+                }
+            }
+        }
+        _ => {
+            // Keeps the comments around the value being replaced.
+            let decor = existing.as_value().map(|value| value.decor().clone());
+            *existing = partial.clone();
+            if let (Some(decor), Some(value)) = (decor, existing.as_value_mut()) {
+                *value.decor_mut() = decor;
+            }
+        }
+    }
+}
+
+/// Merges line by line, so the comments and layout of `existing` are kept.
+fn merge_ini(existing: &str, partial: &str) -> Result<String, Error> {
+    let mut lines: Vec<String> = existing.lines().map(String::from).collect();
+    let mut section: Option<String> = None;
+
+    for (number, line) in partial.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(name) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            section = Some(name.trim().to_string());
+            continue;
+        }
+
+        let (key, value) = line.split_once('=').ok_or_else(|| {
+            Error::Partial(format!("line {}: expected `key = value`", number + 1))
+        })?;
+        set_ini_value(&mut lines, section.as_deref(), key.trim(), value.trim());
+    }
+
+    let mut merged = lines.join("\n");
+    merged.push('\n');
+    Ok(merged)
+}
+
+/// Sets `key` in `section` of the INI file made of `lines`, adding the section at the
+/// end if it does not exist. `None` is the part before the first section.
+fn set_ini_value(lines: &mut Vec<String>, section: Option<&str>, key: &str, value: &str) {
+    let header = |line: &str| {
+        let line = line.trim();
+        line.strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+            .map(|name| name.trim().to_string())
+    };
+
+    let start = match section {
+        None => 0,
+        Some(section) => match lines
+            .iter()
+            .position(|line| header(line).as_deref() == Some(section))
+        {
+            Some(index) => index + 1,
+            None => {
+                if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+                    lines.push(String::new());
+                }
+                lines.push(format!("[{section}]"));
+                lines.push(format!("{key} = {value}"));
+                return;
+            }
+        },
+    };
+    let end = lines[start..]
+        .iter()
+        .position(|line| header(line).is_some())
+        .map_or(lines.len(), |index| start + index);
+
+    for line in &mut lines[start..end] {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with(';') || trimmed.starts_with('#') {
+            continue;
+        }
+
+        if let Some((name, current)) = line.split_once('=') {
+            if name.trim() == key {
+                // Keeps the spacing around `=`.
+                let space = &current[..current.len() - current.trim_start().len()];
+                *line = format!("{name}={space}{value}");
+                return;
+            }
+        }
+    }
+
+    // Adds the key after the last setting of the section, before blank lines.
+    let last = lines[start..end]
+        .iter()
+        .rposition(|line| !line.trim().is_empty())
+        .map_or(start, |index| start + index + 1);
+    lines.insert(last, format!("{key} = {value}"));
+}
+
+fn merge_yaml(existing: &str, partial: &str) -> Result<String, Error> {
+    let parse = |document: &str| -> Result<serde_yaml::Value, String> {
+        if document.trim().is_empty() {
+            return Ok(serde_yaml::Value::Mapping(Default::default()));
+        }
+        serde_yaml::from_str(document).map_err(|err| err.to_string())
+    };
+
+    let mut merged = parse(existing).map_err(Error::Existing)?;
+    let partial = parse(partial).map_err(Error::Partial)?;
+    merge_yaml_value(&mut merged, partial);
+
+    serde_yaml::to_string(&merged).map_err(|err| Error::Serialize(err.to_string()))
+}
+
+fn merge_yaml_value(existing: &mut serde_yaml::Value, partial: serde_yaml::Value) {
+    match (existing, partial) {
+        (serde_yaml::Value::Mapping(existing), serde_yaml::Value::Mapping(partial)) => {
+            for (key, value) in partial {
+                match existing.get_mut(&key) {
+                    Some(current) => merge_yaml_value(current, value),
+                    None => {
+                        existing.insert(key, value);
+                    }
+                }
+            }
+        }
+        (existing, partial) => *existing = partial,
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Existing(err) => write!(f, "the file on disk cannot be merged into: {err}"),
+            Error::Partial(err) => write!(f, "the blueprint's contents cannot be merged: {err}"),
+            Error::Serialize(err) => write!(f, "failed to write the merged file: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_overrides_nested_keys_and_keeps_the_others() {
+        let existing = r#"{
+    "editor": { "fontSize": 12, "tabSize": 4 },
+    "theme": "dark"
+}"#;
+        let partial = r#"{ "editor": { "fontSize": 14, "rulers": [80] } }"#;
+        assert_eq!(
+            merge(existing, partial, Format::Json).unwrap(),
+            r#"{
+    "editor": {
+        "fontSize": 14,
+        "tabSize": 4,
+        "rulers": [
+            80
+        ]
+    },
+    "theme": "dark"
+}
+"#
+        );
+    }
+
+    #[test]
+    fn json_comments_and_trailing_commas() {
+        let existing = r#"{
+  // Where files are opened.
+  "url": "https://example.com//path", /* not a comment: */
+  "pattern": "a,}b/*c*/",
+  "list": [1, 2,],
+}"#;
+        let merged = merge(existing, r#"{ "added": true, }"#, Format::Json).unwrap();
+        let merged: serde_json::Value = serde_json::from_str(&merged).unwrap();
+        assert_eq!(
+            merged,
+            serde_json::json!({
+                "url": "https://example.com//path",
+                "pattern": "a,}b/*c*/",
+                "list": [1, 2],
+                "added": true,
+            })
+        );
+    }
+
+    #[test]
+    fn toml_overrides_nested_keys_and_keeps_comments() {
+        let existing = "# My settings\n[editor]\n# In points.\nfont-size = 12 # small\ntab-size = 4\n\n[theme]\nname = \"dark\"\n";
+        let partial = "[editor]\nfont-size = 14\nrulers = [80]\n";
+        assert_eq!(
+            merge(existing, partial, Format::Toml).unwrap(),
+            "# My settings\n[editor]\n# In points.\nfont-size = 14 # small\ntab-size = 4\nrulers = [80]\n\n[theme]\nname = \"dark\"\n"
+        );
+    }
+
+    #[test]
+    fn ini_keys_before_the_first_section() {
+        let existing = "; global\nverbose = false\n\n[user]\nname = me\n";
+        let partial =
+            "verbose = true\ncolor = auto\n[user]\nemail=me@example.com\n[core]\neditor = vim\n";
+        assert_eq!(
+            merge(existing, partial, Format::Ini).unwrap(),
+            "; global\nverbose = true\ncolor = auto\n\n[user]\nname = me\nemail = me@example.com\n\n[core]\neditor = vim\n"
+        );
+    }
+
+    #[test]
+    fn ini_keeps_spacing_and_skips_commented_keys() {
+        let existing = "[user]\n;name = old\nname=me\n";
+        assert_eq!(
+            merge(existing, "[user]\nname = you\n", Format::Ini).unwrap(),
+            "[user]\n;name = old\nname=you\n"
+        );
+    }
+
+    #[test]
+    fn yaml_overrides_nested_keys() {
+        let existing = "editor:\n  font: 12\n  tabs: 4\ntheme: dark\n";
+        let merged = merge(existing, "editor:\n  font: 14\n", Format::Yaml).unwrap();
+        assert_eq!(merged, "editor:\n  font: 14\n  tabs: 4\ntheme: dark\n");
+    }
+
+    #[test]
+    fn empty_files_are_empty_documents() {
+        for format in [Format::Json, Format::Toml, Format::Ini, Format::Yaml] {
+            assert!(merge("", "", format).is_ok(), "{format:?}");
+        }
+        assert_eq!(
+            merge("", "{\"a\": 1}", Format::Json).unwrap(),
+            "{\n  \"a\": 1\n}\n"
+        );
+    }
+
+    #[test]
+    fn invalid_input_is_an_error() {
+        let cases = [
+            (Format::Json, "{ \"a\": ", "{}"),
+            (Format::Toml, "a = ", "b = 1"),
+            (Format::Yaml, "a: [1", "b: 1"),
+        ];
+        for (format, invalid, valid) in cases {
+            assert!(
+                matches!(merge(invalid, valid, format), Err(Error::Existing(_))),
+                "{format:?}"
+            );
+            assert!(
+                matches!(merge(valid, invalid, format), Err(Error::Partial(_))),
+                "{format:?}"
+            );
+        }
+        assert!(matches!(
+            merge("", "[user]\nname\n", Format::Ini),
+            Err(Error::Partial(_))
+        ));
+    }
+}
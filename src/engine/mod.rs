@@ -0,0 +1,403 @@
+// SPDX-License-Identifier: GPL-3.0
+
+//! Turns the packages of a blueprint into package manager calls and config files.
+
+use std::path::PathBuf;
+
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::sync::mpsc::UnboundedSender;
+use uuid::Uuid;
+
+use crate::app::models::package::{Package, Source};
+
+pub mod deploy;
+pub mod detect;
+pub mod drift;
+pub mod helper;
+pub mod host;
+pub mod installer;
+pub mod journal;
+pub mod merge;
+pub mod plan;
+pub mod revert;
+pub mod scan;
+pub mod template;
+
+use deploy::{Deployment, Outcome};
+use helper::{Elevation, Helper, Request, Verb};
+use installer::Invocation;
+use journal::{Journal, Record};
+use plan::{Action, FileAction, FileStep, PackageStep, Plan};
+
+/// Progress reported while the engine runs.
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// The run started with this many packages.
+    Started(usize),
+    /// A package is about to be installed.
+    Installing(Uuid),
+    /// A package is about to be removed.
+    Removing(Uuid),
+    /// A line printed by the package manager.
+    Output(Uuid, String),
+    /// A package was installed successfully.
+    Installed(Uuid),
+    /// A package was removed successfully.
+    Removed(Uuid),
+    /// A package could not be installed or removed.
+    Failed(Uuid, String),
+    /// A config file was written.
+    Deployed(Deployment),
+    /// A config file of a package was put back as it was before Blueprint wrote it.
+    Restored(PathBuf),
+    /// A config file of a package could not be written.
+    DeployFailed(Uuid, PathBuf, String),
+    /// Every package has been processed.
+    Finished(Report),
+}
+
+/// The outcome of a run.
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub installed: Vec<Uuid>,
+    pub removed: Vec<Uuid>,
+    pub deployed: Vec<Deployment>,
+    /// Config files put back as they were, each with the backup restored.
+    pub restored: Vec<Deployment>,
+    pub failed: Vec<(Uuid, String)>,
+}
+
+impl Report {
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+pub struct Engine {
+    /// Identifies the run in the journal.
+    id: Uuid,
+    steps: Vec<PackageStep>,
+    files: Vec<FileStep>,
+    journal: Option<Journal>,
+    elevation: Elevation,
+    /// The helper running privileged calls, started by the first one. Holds why it
+    /// could not be started, so the user is only asked once.
+    helper: tokio::sync::Mutex<Option<Result<Helper, String>>>,
+}
+
+impl Engine {
+    /// Creates an engine for the approved steps of `plan` and the approved config
+    /// files of their packages, recording what it does in the user's journal.
+    pub fn new(plan: Plan) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            journal: Journal::open(),
+            elevation: Elevation::default(),
+            helper: tokio::sync::Mutex::new(None),
+            steps: plan.pending().cloned().collect(),
+            files: plan.pending_files().cloned().collect(),
+        }
+    }
+
+    /// Gets root for privileged calls with `elevation` rather than with `pkexec`.
+    pub fn elevate_with(mut self, elevation: Elevation) -> Self {
+        self.elevation = elevation;
+        self
+    }
+
+    /// Runs every step in order, reporting progress through `events`.
+    pub async fn run(self, events: UnboundedSender<Event>) -> Report {
+        let mut report = Report::default();
+        self.record(Record::Started);
+        _ = events.send(Event::Started(self.steps.len()));
+
+        for step in &self.steps {
+            let package = &step.package;
+            let result = match step.action {
+                Action::Install => {
+                    _ = events.send(Event::Installing(package.id));
+                    self.execute(package, Verb::Install, &events).await
+                }
+                Action::Remove => {
+                    _ = events.send(Event::Removing(package.id));
+                    self.execute(package, Verb::Remove, &events).await
+                }
+                Action::Bootstrap => {
+                    _ = events.send(Event::Installing(package.id));
+                    self.call(
+                        package,
+                        Verb::Install,
+                        vec![installer::bootstrap_rustup()],
+                        &events,
+                    )
+                    .await
+                }
+                Action::Skip | Action::Unavailable | Action::Invalid => continue,
+            };
+
+            match (result, step.action) {
+                // rustup is left in place when the run is reverted, so only the call
+                // that installed it is journaled.
+                (Ok(()), Action::Bootstrap) => {
+                    _ = events.send(Event::Installed(package.id));
+                }
+                (Ok(()), Action::Remove) => {
+                    self.record(Record::Removed {
+                        package: package.id,
+                        name: package.name.clone(),
+                        source: package.source.clone(),
+                    });
+                    report.removed.push(package.id);
+                    _ = events.send(Event::Removed(package.id));
+                }
+                (Ok(()), _) => {
+                    self.record(Record::Installed {
+                        package: package.id,
+                        name: package.name.clone(),
+                        source: package.source.clone(),
+                    });
+                    report.installed.push(package.id);
+                    _ = events.send(Event::Installed(package.id));
+                }
+                (Err(err), _) => {
+                    log::error!("failed to apply {}: {}", package.name, err);
+                    self.record(Record::Failed {
+                        package: package.id,
+                        error: err.clone(),
+                    });
+                    report.failed.push((package.id, err.clone()));
+                    _ = events.send(Event::Failed(package.id, err));
+                }
+            }
+        }
+
+        // Config files are only written once their package is in place, so none are
+        // written for a package whose step failed.
+        for file in &self.files {
+            if report.failed.iter().any(|(id, _)| *id == file.package) {
+                continue;
+            }
+
+            let FileStep {
+                package,
+                target,
+                action,
+                content,
+                mode,
+                link,
+                backup,
+                ..
+            } = file.clone();
+            let restore = action == FileAction::Restore;
+            let result = tokio::task::spawn_blocking({
+                let target = target.clone();
+                let backup = backup.clone();
+                move || match (restore, link) {
+                    (true, link) => deploy::restore(&target, backup.as_deref(), link.as_deref())
+                        .map(|()| Outcome::Written(None)),
+                    (false, Some(managed)) => deploy::link(&target, &managed, &content, mode),
+                    (false, None) => deploy::deploy(&target, &content, mode),
+                }
+            })
+            .await
+            .map_err(|err| err.to_string())
+            .and_then(|result| result.map_err(|err| err.to_string()));
+
+            match result {
+                Ok(_) if restore => {
+                    self.record(Record::Restored {
+                        target: target.clone(),
+                    });
+                    report.restored.push(Deployment {
+                        package,
+                        target: target.clone(),
+                        backup,
+                    });
+                    _ = events.send(Event::Restored(target));
+                }
+                // Nothing was written, so there is nothing to roll back.
+                Ok(Outcome::Unchanged) => {}
+                Ok(Outcome::Written(backup)) => {
+                    self.record(Record::Written {
+                        package,
+                        target: target.clone(),
+                        backup: backup.clone(),
+                    });
+                    let deployment = Deployment {
+                        package,
+                        target,
+                        backup,
+                    };
+                    report.deployed.push(deployment.clone());
+                    _ = events.send(Event::Deployed(deployment));
+                }
+                Err(err) => {
+                    log::error!("failed to write {}: {}", target.display(), err);
+                    self.record(Record::Failed {
+                        package,
+                        error: format!("{}: {err}", target.display()),
+                    });
+                    let error = format!("{}: {err}", target.display());
+                    report.failed.push((package, error));
+                    _ = events.send(Event::DeployFailed(package, target, err));
+                }
+            }
+        }
+
+        self.record(Record::Finished);
+        _ = events.send(Event::Finished(report.clone()));
+        report
+    }
+
+    /// Adds `record` to the journal, if there is one.
+    fn record(&self, record: Record) {
+        if let Some(journal) = &self.journal {
+            if let Err(err) = journal.append(self.id, record) {
+                log::error!("failed to write to the journal: {}", err);
+            }
+        }
+    }
+
+    async fn execute(
+        &self,
+        package: &Package,
+        verb: Verb,
+        events: &UnboundedSender<Event>,
+    ) -> Result<(), String> {
+        if let (Verb::Remove, Source::Go { package: go, .. }) = (verb, &package.source) {
+            let go = go.clone();
+            let binary = tokio::task::spawn_blocking(move || installer::remove_go_binary(&go))
+                .await
+                .map_err(|err| err.to_string())??;
+            let removed = format!("Removed {}", binary.display());
+            _ = events.send(Event::Output(package.id, removed));
+            return Ok(());
+        }
+
+        let invocations = match verb {
+            Verb::Install => package.source.install(),
+            Verb::Remove => package.source.remove(),
+        };
+        self.call(package, verb, invocations, events).await
+    }
+
+    /// Makes the package manager `invocations` that `verb` `package`, in order,
+    /// stopping at the first one that fails.
+    async fn call(
+        &self,
+        package: &Package,
+        verb: Verb,
+        invocations: Vec<Invocation>,
+        events: &UnboundedSender<Event>,
+    ) -> Result<(), String> {
+        for invocation in invocations {
+            _ = events.send(Event::Output(package.id, format!("$ {invocation}")));
+            let command = |status: Option<i32>| Record::Command {
+                package: package.id,
+                source: package.source.clone(),
+                command: invocation.to_string(),
+                status,
+            };
+
+            let result = if invocation.privileged && !installer::is_root() {
+                self.run_privileged(package, verb, events).await
+            } else {
+                run(package, &invocation, events).await
+            };
+            let code = result.inspect_err(|_| self.record(command(None)))?;
+
+            self.record(command(code));
+            match code {
+                Some(0) => {}
+                Some(code) => return Err(format!("`{invocation}` exited with status {code}")),
+                None => return Err(format!("`{invocation}` was stopped by a signal")),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Asks the helper to `verb` `package`, starting it if this is the first
+    /// privileged call of the run.
+    async fn run_privileged(
+        &self,
+        package: &Package,
+        verb: Verb,
+        events: &UnboundedSender<Event>,
+    ) -> Result<Option<i32>, String> {
+        let request = Request::new(verb, &package.source).ok_or_else(|| {
+            format!(
+                "{} packages are not installed as root",
+                package.source.kind()
+            )
+        })?;
+
+        let mut helper = self.helper.lock().await;
+        let helper = helper.get_or_insert_with(|| Helper::spawn(self.elevation));
+        let running = match &mut *helper {
+            Ok(running) => running,
+            Err(err) => return Err(err.clone()),
+        };
+
+        let result = running
+            .run(&request, |line| {
+                _ = events.send(Event::Output(package.id, line));
+            })
+            .await;
+
+        // A helper that stopped answering cannot run the remaining calls.
+        if let Err(err) = &result {
+            if running.is_closed() {
+                *helper = Err(err.clone());
+            }
+        }
+
+        result
+    }
+}
+
+/// Runs `invocation` as the current user, forwarding what it prints as output of
+/// `package`, and returns its exit code, if it has one.
+async fn run(
+    package: &Package,
+    invocation: &Invocation,
+    events: &UnboundedSender<Event>,
+) -> Result<Option<i32>, String> {
+    let mut child = invocation
+        .command()
+        .spawn()
+        .map_err(|err| format!("failed to run {}: {err}", invocation.program))?;
+
+    let stdout = child
+        .stdout
+        .take()
+        .map(|out| forward(out, package.id, events));
+    let stderr = child
+        .stderr
+        .take()
+        .map(|err| forward(err, package.id, events));
+    let (status, _, _) = tokio::join!(
+        child.wait(),
+        async {
+            if let Some(stdout) = stdout {
+                stdout.await
+            }
+        },
+        async {
+            if let Some(stderr) = stderr {
+                stderr.await
+            }
+        }
+    );
+
+    status
+        .map(|status| status.code())
+        .map_err(|err| err.to_string())
+}
+
+async fn forward(output: impl AsyncRead + Unpin, id: Uuid, events: &UnboundedSender<Event>) {
+    let mut lines = BufReader::new(output).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        _ = events.send(Event::Output(id, line));
+    }
+}
@@ -0,0 +1,536 @@
+// SPDX-License-Identifier: GPL-3.0
+
+//! Computes what applying a blueprint would change before anything is touched.
+
+use std::path::{Path, PathBuf};
+
+use similar::TextDiff;
+use uuid::Uuid;
+
+use crate::app::models::package::{ConfigFile, DeployMethod, Kind, Package, Source};
+use crate::app::Page;
+
+use super::deploy::{self, LinkStatus};
+use super::detect::{Installed, Status};
+use super::drift::Drift;
+use super::host::HostInfo;
+use super::revert::Applied;
+use super::template::Variables;
+
+/// Everything applying a blueprint would do, for review before execution.
+#[derive(Debug, Clone, Default)]
+pub struct Plan {
+    pub packages: Vec<PackageStep>,
+    pub files: Vec<FileStep>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PackageStep {
+    pub package: Package,
+    pub action: Action,
+    /// Whether the step will run when the plan is executed.
+    pub approved: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// The package is missing or outdated and will be installed.
+    Install,
+    /// The package is already present.
+    Skip,
+    /// The package is disabled but present and can be removed.
+    Remove,
+    /// The package is missing but none of its sources can be used on this machine.
+    Unavailable,
+    /// The package does not [validate](Package::validate) and is left alone.
+    Invalid,
+    /// rustup is missing and its distribution does not package it, so it is
+    /// downloaded and installed with its official installer.
+    Bootstrap,
+}
+
+#[derive(Debug, Clone)]
+pub struct FileStep {
+    /// The package that owns the file.
+    pub package: Uuid,
+    pub target: PathBuf,
+    pub action: FileAction,
+    /// What the file will contain, with its placeholders filled in.
+    pub content: String,
+    pub mode: Option<u32>,
+    /// The managed copy `target` links to, for files deployed as symlinks.
+    pub link: Option<PathBuf>,
+    /// The backup put back in place of `target` when it is restored, if it existed
+    /// before Blueprint wrote it.
+    pub backup: Option<PathBuf>,
+    /// A unified diff from the current contents of `target` to the blueprint's, or
+    /// why the file cannot be written if it is invalid.
+    pub diff: String,
+    /// Whether the file will be written when the plan is executed, provided its
+    /// package is in place.
+    pub approved: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileAction {
+    Create,
+    Overwrite,
+    Unchanged,
+    /// The file cannot be rendered or merged and is left alone.
+    Invalid,
+    /// The file is put back as it was before Blueprint wrote it, or removed if it did
+    /// not exist.
+    Restore,
+}
+
+impl Plan {
+    /// Plans `packages` against the `installed` state of `host`, filling templated
+    /// config files in with `variables` and merging merged ones into their targets.
+    ///
+    /// Each step's package carries the source chosen for `host`. Installs are approved
+    /// by default; removals must be approved explicitly. Toolchains that need rustup
+    /// when it is missing come after a step that installs it, from the distribution
+    /// if it packages rustup, otherwise with the official installer, which must be
+    /// approved explicitly too.
+    pub fn new(
+        packages: &[Package],
+        installed: &Installed,
+        host: &HostInfo,
+        variables: &Variables,
+    ) -> Self {
+        let mut plan = Plan::default();
+        let mut rustup_planned = false;
+
+        for package in packages {
+            let mut package = package.clone();
+            package.source = host.source_for(&package).clone();
+
+            // Names are passed to package managers running as root, so a package
+            // that does not validate is never turned into a call.
+            if !package.validate().is_empty() {
+                plan.packages.push(PackageStep {
+                    package,
+                    action: Action::Invalid,
+                    approved: false,
+                });
+                continue;
+            }
+
+            let status = installed.status(&package.source);
+            let action = match (package.enabled, status) {
+                (true, Status::Installed) => Action::Skip,
+                (true, _) if !host.supports(&package.source) => Action::Unavailable,
+                (true, Status::Missing | Status::Outdated { .. }) => Action::Install,
+                (false, Status::Installed | Status::Outdated { .. }) => Action::Remove,
+                (false, Status::Missing) => continue,
+            };
+
+            if action == Action::Install
+                && package.source.kind() == Kind::Rustup
+                && !host.managers.contains(&Kind::Rustup)
+                && !rustup_planned
+            {
+                plan.packages.push(rustup_step(host));
+                rustup_planned = true;
+            }
+
+            plan.packages.push(PackageStep {
+                package: package.clone(),
+                action,
+                approved: action == Action::Install,
+            });
+
+            if !package.enabled {
+                continue;
+            }
+
+            for file in &package.config {
+                let target = file.target_path();
+                let link = match file.method {
+                    DeployMethod::Copy | DeployMethod::Merge => None,
+                    DeployMethod::Symlink => deploy::managed_path(package.id, &target),
+                };
+                let linked = link.as_ref().map_or(true, |link| {
+                    deploy::link_status(&target, link) == LinkStatus::Linked
+                });
+
+                let current = std::fs::read_to_string(&target).ok();
+                let content = match deploy::expected(file, current.as_deref(), variables) {
+                    Ok(content) => content,
+                    Err(err) => {
+                        plan.files.push(FileStep {
+                            package: package.id,
+                            target,
+                            action: FileAction::Invalid,
+                            content: file.content.clone(),
+                            mode: file.mode,
+                            link,
+                            backup: None,
+                            diff: err,
+                            approved: false,
+                        });
+                        continue;
+                    }
+                };
+
+                let has_mode = std::fs::metadata(&target)
+                    .is_ok_and(|metadata| deploy::has_mode(&metadata, file.mode));
+                let action = match &current {
+                    None => FileAction::Create,
+                    Some(current) if *current == content && linked && has_mode => {
+                        FileAction::Unchanged
+                    }
+                    Some(_) => FileAction::Overwrite,
+                };
+
+                let diff = unified_diff(&target, current.as_deref().unwrap_or_default(), &content);
+
+                plan.files.push(FileStep {
+                    package: package.id,
+                    target,
+                    action,
+                    content,
+                    mode: file.mode,
+                    link,
+                    backup: None,
+                    diff,
+                    approved: action != FileAction::Unchanged,
+                });
+            }
+        }
+
+        plan
+    }
+
+    /// Plans undoing what Blueprint `applied`: removing the packages it installed that
+    /// are still `installed`, and restoring the files it wrote. Every step is approved.
+    pub fn revert(applied: &Applied, installed: &Installed) -> Self {
+        let mut plan = Plan::default();
+
+        for package in &applied.packages {
+            if !package.source.has_valid_name()
+                || installed.status(&package.source) == Status::Missing
+            {
+                continue;
+            }
+
+            plan.packages.push(PackageStep {
+                package: package.clone(),
+                action: Action::Remove,
+                approved: true,
+            });
+        }
+
+        for deployment in &applied.files {
+            let target = deployment.target.clone();
+            let link = deploy::managed_path(deployment.package, &target)
+                .filter(|link| deploy::link_status(&target, link) == LinkStatus::Linked);
+
+            let current = std::fs::read_to_string(&target).unwrap_or_default();
+            let (action, content, diff) = match &deployment.backup {
+                Some(backup) => match std::fs::read_to_string(backup) {
+                    Ok(content) => (FileAction::Restore, content, None),
+                    Err(err) => (
+                        FileAction::Invalid,
+                        String::new(),
+                        Some(format!(
+                            "the backup at {} cannot be read: {err}",
+                            backup.display()
+                        )),
+                    ),
+                },
+                // Nothing to remove if the file is already gone.
+                None if target.symlink_metadata().is_err() => {
+                    (FileAction::Unchanged, String::new(), None)
+                }
+                None => (FileAction::Restore, String::new(), None),
+            };
+
+            let diff = diff.unwrap_or_else(|| unified_diff(&target, &current, &content));
+
+            plan.files.push(FileStep {
+                package: deployment.package,
+                target,
+                action,
+                content,
+                mode: None,
+                link,
+                backup: deployment.backup.clone(),
+                diff,
+                approved: action == FileAction::Restore,
+            });
+        }
+
+        plan
+    }
+
+    /// Plans writing `file` of the package `package` back over its target, which
+    /// `drift`ed from the blueprint. The step is approved.
+    pub fn overwrite(package: Uuid, file: &ConfigFile, drift: &Drift) -> Self {
+        let link = match file.method {
+            DeployMethod::Copy | DeployMethod::Merge => None,
+            DeployMethod::Symlink => deploy::managed_path(package, &drift.target),
+        };
+
+        Plan {
+            packages: vec![],
+            files: vec![FileStep {
+                package,
+                target: drift.target.clone(),
+                action: FileAction::Overwrite,
+                content: drift.expected.clone(),
+                mode: file.mode,
+                link,
+                backup: None,
+                diff: unified_diff(&drift.target, &drift.current, &drift.expected),
+                approved: true,
+            }],
+        }
+    }
+
+    /// The package steps that will run when the plan is executed.
+    pub fn pending(&self) -> impl Iterator<Item = &PackageStep> {
+        self.packages.iter().filter(|step| {
+            step.approved
+                && matches!(
+                    step.action,
+                    Action::Install | Action::Remove | Action::Bootstrap
+                )
+        })
+    }
+
+    /// The file steps that will run when the plan is executed: the approved ones
+    /// whose package is already present or has an approved step to install or remove
+    /// it. Files of packages that are unavailable, invalid or not approved are left
+    /// alone.
+    pub fn pending_files(&self) -> impl Iterator<Item = &FileStep> {
+        self.files.iter().filter(|file| {
+            let writes = matches!(
+                file.action,
+                FileAction::Create | FileAction::Overwrite | FileAction::Restore
+            );
+            let package = self
+                .packages
+                .iter()
+                .find(|step| step.package.id == file.package);
+            let ready = match package.map(|step| (step.action, step.approved)) {
+                None | Some((Action::Skip, _)) => true,
+                Some((Action::Install | Action::Remove | Action::Bootstrap, approved)) => approved,
+                Some((Action::Unavailable | Action::Invalid, _)) => false,
+            };
+            file.approved && writes && ready
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending().next().is_none() && self.pending_files().next().is_none()
+    }
+}
+
+/// The step that installs rustup on `host`, where it is missing.
+fn rustup_step(host: &HostInfo) -> PackageStep {
+    match host.rustup_package() {
+        Some(source) => PackageStep {
+            package: Package::new("rustup", source, Page::Languages),
+            action: Action::Install,
+            approved: true,
+        },
+        // Running a script from the internet is left to the user to approve.
+        None => PackageStep {
+            package: Package::new(
+                "rustup",
+                Source::Rustup {
+                    toolchain: "none".into(),
+                    targets: vec![],
+                    components: vec![],
+                },
+                Page::Languages,
+            ),
+            action: Action::Bootstrap,
+            approved: false,
+        },
+    }
+}
+
+/// A unified diff of `target` from `old` to `new`.
+fn unified_diff(target: &Path, old: &str, new: &str) -> String {
+    let name = target.display().to_string();
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .header(&name, &name)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use crate::engine::host::OsRelease;
+
+    use super::*;
+
+    fn package(name: &str, target: PathBuf) -> Package {
+        let mut package = Package::new(name, Source::Apt(name.into()), Page::Tools);
+        package.config.push(ConfigFile::new(target, "set -x\n"));
+        package
+    }
+
+    fn plan(packages: &[Package], installed: &Installed) -> Plan {
+        let host = HostInfo {
+            managers: vec![Kind::Apt],
+            ..Default::default()
+        };
+        Plan::new(packages, installed, &host, &Variables::default())
+    }
+
+    #[test]
+    fn files_follow_their_package_step() {
+        let dir = tempfile::tempdir().unwrap();
+        let installed = Installed {
+            dpkg: [("fish".to_string(), "3.7.0".to_string())].into(),
+            ..Default::default()
+        };
+        let packages = [
+            package("fish", dir.path().join("fish")),
+            package("zsh", dir.path().join("zsh")),
+        ];
+
+        let mut plan = plan(&packages, &installed);
+        let pending = |plan: &Plan| -> Vec<PathBuf> {
+            plan.pending_files()
+                .map(|file| file.target.clone())
+                .collect()
+        };
+        assert_eq!(
+            pending(&plan),
+            [dir.path().join("fish"), dir.path().join("zsh")]
+        );
+
+        // Not installing zsh leaves its file alone.
+        plan.packages[1].approved = false;
+        assert_eq!(pending(&plan), [dir.path().join("fish")]);
+
+        plan.files[0].approved = false;
+        assert!(plan.is_empty());
+    }
+
+    #[test]
+    fn files_of_unavailable_packages_are_not_written() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut package = package("fish", dir.path().join("fish"));
+        package.source = Source::Pacman("fish".into());
+
+        let plan = plan(&[package], &Installed::default());
+        assert_eq!(plan.packages[0].action, Action::Unavailable);
+        assert!(plan.files[0].approved);
+        assert_eq!(plan.pending_files().count(), 0);
+    }
+
+    #[test]
+    fn overwriting_a_drifted_file_plans_only_that_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let package = package("fish", dir.path().join("fish"));
+        let drift = Drift {
+            index: 0,
+            target: dir.path().join("fish"),
+            expected: "set -x\n".into(),
+            current: "set -e\n".into(),
+        };
+
+        let plan = Plan::overwrite(package.id, &package.config[0], &drift);
+        assert_eq!(plan.pending().count(), 0);
+        let files: Vec<_> = plan.pending_files().collect();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].action, FileAction::Overwrite);
+        assert_eq!(files[0].content, "set -x\n");
+    }
+
+    #[test]
+    fn missing_rustup_is_installed_first() {
+        let toolchain = |name: &str| Package::new(name, Kind::Rustup.source(name), Page::Languages);
+        let packages = [toolchain("stable"), toolchain("nightly")];
+        let steps = |host: &HostInfo| -> Vec<(String, Action, bool)> {
+            Plan::new(
+                &packages,
+                &Installed::default(),
+                host,
+                &Variables::default(),
+            )
+            .packages
+            .into_iter()
+            .map(|step| (step.package.source.to_string(), step.action, step.approved))
+            .collect()
+        };
+
+        let arch = HostInfo {
+            os: OsRelease::parse("ID=arch\n"),
+            managers: vec![Kind::Pacman],
+            ..Default::default()
+        };
+        assert_eq!(
+            steps(&arch),
+            [
+                ("pacman:rustup".into(), Action::Install, true),
+                ("rustup:stable".into(), Action::Install, true),
+                ("rustup:nightly".into(), Action::Install, true),
+            ]
+        );
+
+        // The official installer must be approved.
+        let bookworm = HostInfo {
+            os: OsRelease::parse("ID=debian\nVERSION_ID=\"12\"\n"),
+            managers: vec![Kind::Apt],
+            curl: true,
+        };
+        assert_eq!(
+            steps(&bookworm)[..2],
+            [
+                ("rustup:none".into(), Action::Bootstrap, false),
+                ("rustup:stable".into(), Action::Install, true),
+            ]
+        );
+
+        let rustup = HostInfo {
+            managers: vec![Kind::Rustup],
+            ..Default::default()
+        };
+        assert_eq!(steps(&rustup).len(), 2);
+
+        let neither = HostInfo {
+            os: OsRelease::parse("ID=debian\nVERSION_ID=\"12\"\n"),
+            managers: vec![Kind::Apt],
+            curl: false,
+        };
+        assert_eq!(steps(&neither)[0].1, Action::Unavailable);
+    }
+
+    #[test]
+    fn files_with_another_mode_are_overwritten() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("fish");
+        std::fs::write(&target, "set -x\n").unwrap();
+        std::fs::set_permissions(&target, std::fs::Permissions::from_mode(0o644)).unwrap();
+        let installed = Installed {
+            dpkg: [("fish".to_string(), "3.7.0".to_string())].into(),
+            ..Default::default()
+        };
+
+        let mut package = package("fish", target.clone());
+        assert_eq!(
+            plan(&[package.clone()], &installed).files[0].action,
+            FileAction::Unchanged
+        );
+
+        package.config[0].mode = Some(0o600);
+        assert_eq!(
+            plan(&[package.clone()], &installed).files[0].action,
+            FileAction::Overwrite
+        );
+
+        std::fs::set_permissions(&target, std::fs::Permissions::from_mode(0o600)).unwrap();
+        assert_eq!(
+            plan(&[package], &installed).files[0].action,
+            FileAction::Unchanged
+        );
+    }
+}
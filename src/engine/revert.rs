@@ -0,0 +1,118 @@
+// SPDX-License-Identifier: GPL-3.0
+
+//! Keeps track of what Blueprint changed on a machine, so it can be reverted.
+
+use serde::{Deserialize, Serialize};
+
+use crate::app::models::package::Package;
+
+use super::deploy::Deployment;
+use super::Report;
+
+/// The packages and config files Blueprint put on this machine.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Applied {
+    /// Packages Blueprint installed, each with the source it was installed from.
+    pub packages: Vec<Package>,
+    /// Config files Blueprint wrote, each with the backup made the first time, which
+    /// holds what the user had before.
+    pub files: Vec<Deployment>,
+}
+
+impl Applied {
+    /// Records the outcome of a run of `packages`.
+    ///
+    /// Installed packages and newly written files are added; removed packages and
+    /// restored files are forgotten. A file written again keeps its first backup, and
+    /// is only forgotten once that backup is restored: rolling back a later run puts
+    /// back what Blueprint wrote before, not what the user had.
+    pub fn record(&mut self, packages: &[Package], report: &Report) {
+        for id in &report.installed {
+            let Some(package) = packages.iter().find(|package| package.id == *id) else {
+                continue;
+            };
+            self.packages.retain(|applied| applied.id != *id);
+            // Config files are tracked in `files`.
+            self.packages.push(Package {
+                config: vec![],
+                ..package.clone()
+            });
+        }
+
+        self.packages
+            .retain(|package| !report.removed.contains(&package.id));
+
+        for deployment in &report.deployed {
+            if !self
+                .files
+                .iter()
+                .any(|file| file.target == deployment.target)
+            {
+                self.files.push(deployment.clone());
+            }
+        }
+
+        self.files.retain(|file| {
+            !report
+                .restored
+                .iter()
+                .any(|restored| restored.target == file.target && restored.backup == file.backup)
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.packages.is_empty() && self.files.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn deployment(package: Uuid, backup: Option<&str>) -> Deployment {
+        Deployment {
+            package,
+            target: PathBuf::from("/home/me/.gitconfig"),
+            backup: backup.map(PathBuf::from),
+        }
+    }
+
+    #[test]
+    fn files_keep_their_first_backup() {
+        let package = Uuid::new_v4();
+        let first = deployment(package, Some("/home/me/.gitconfig.1.bak"));
+        let second = deployment(package, Some("/home/me/.gitconfig.2.bak"));
+        let mut applied = Applied::default();
+
+        for deployed in [&first, &second] {
+            let report = Report {
+                deployed: vec![deployed.clone()],
+                ..Default::default()
+            };
+            applied.record(&[], &report);
+        }
+        assert_eq!(applied.files.len(), 1);
+        assert_eq!(applied.files[0], first);
+
+        // Rolling the second run back puts back what the first one wrote.
+        let report = Report {
+            restored: vec![second],
+            ..Default::default()
+        };
+        applied.record(&[], &report);
+        assert_eq!(applied.files.len(), 1);
+        assert_eq!(applied.files[0], first);
+
+        // Reverting puts back what the user had.
+        let report = Report {
+            restored: vec![first],
+            ..Default::default()
+        };
+        applied.record(&[], &report);
+        assert!(applied.is_empty());
+    }
+}
@@ -0,0 +1,351 @@
+// SPDX-License-Identifier: GPL-3.0
+
+//! Lists the packages the user installed on a machine, to start a blueprint from.
+
+use std::collections::HashSet;
+use std::path::Path;
+use std::process::Command;
+
+use crate::app::models::package::{Kind, Package, Source};
+use crate::app::Page;
+
+use super::detect::{
+    capture, cargo_home, channel, global_node_modules, go_bin_dir, go_binary, parse_crates2,
+    parse_go_version, parse_pipx_list, parse_toolchains, parse_uv_tool_list, read_node_modules,
+    user_command,
+};
+use super::host::HostInfo;
+
+const SHELLS: [&str; 10] = [
+    "bash", "zsh", "fish", "nushell", "nu", "tcsh", "ksh", "dash", "elvish", "xonsh",
+];
+const LANGUAGES: [&str; 30] = [
+    "python",
+    "nodejs",
+    "node",
+    "npm",
+    "pnpm",
+    "typescript",
+    "golang",
+    "go",
+    "rust",
+    "rustc",
+    "rustup",
+    "cargo",
+    "ruby",
+    "php",
+    "java",
+    "openjdk",
+    "jdk",
+    "gcc",
+    "clang",
+    "llvm",
+    "ghc",
+    "erlang",
+    "elixir",
+    "lua",
+    "perl",
+    "dotnet",
+    "kotlin",
+    "zig",
+    "julia",
+    "deno",
+];
+const EDITORS: [&str; 16] = [
+    "vim",
+    "gvim",
+    "neovim",
+    "nvim",
+    "emacs",
+    "nano",
+    "micro",
+    "helix",
+    "kakoune",
+    "code",
+    "codium",
+    "vscodium",
+    "zed",
+    "gedit",
+    "kate",
+    "texteditor",
+];
+
+/// The packages installed explicitly with each package manager available on `host`,
+/// each put on the page [`guess_page`] picks for it.
+///
+/// Packages pulled in as dependencies are left out, and so are the packages Debian
+/// and Ubuntu mark as part of the base system.
+pub fn scan(host: &HostInfo) -> Vec<Package> {
+    let mut packages = vec![];
+
+    for kind in &host.managers {
+        let names = match kind {
+            Kind::Apt => capture(Command::new("apt-mark").arg("showmanual")).map(|output| {
+                let base = std::fs::read_to_string(Path::new("/var/lib/dpkg/status"))
+                    .map(|status| parse_dpkg_base(&status))
+                    .unwrap_or_default();
+                parse_names(&output)
+                    .into_iter()
+                    .filter(|name| !base.contains(name))
+                    .collect()
+            }),
+            Kind::Dnf => capture(Command::new("dnf").args([
+                "repoquery",
+                "--userinstalled",
+                "--queryformat",
+                "%{name}\n",
+            ]))
+            .map(|output| parse_names(&output)),
+            Kind::Pacman => {
+                capture(Command::new("pacman").arg("-Qqe")).map(|output| parse_names(&output))
+            }
+            // zypper does not tell packages the user asked for from dependencies.
+            Kind::Zypper => None,
+            Kind::Cargo => cargo_home()
+                .and_then(|home| std::fs::read_to_string(home.join(".crates2.json")).ok())
+                .map(|crates| {
+                    let mut names: Vec<String> = parse_crates2(&crates).into_keys().collect();
+                    names.sort();
+                    names
+                }),
+            Kind::Flatpak => {
+                let apps = capture(Command::new("flatpak").args([
+                    "list",
+                    "--app",
+                    "--columns=name,application",
+                ]));
+                for (name, id) in apps
+                    .map(|output| parse_flatpak_apps(&output))
+                    .unwrap_or_default()
+                {
+                    let page = guess_page(id.rsplit('.').next().unwrap_or(&id));
+                    let source = Source::Flatpak {
+                        id,
+                        version: String::new(),
+                    };
+                    packages.push(Package::new(&name, source, page));
+                }
+                continue;
+            }
+            // Tools are listed by their normalized name, which pipx and uv accept.
+            Kind::Pipx => capture(user_command("pipx").args(["list", "--json"])).map(|output| {
+                let mut names: Vec<String> = parse_pipx_list(&output).into_keys().collect();
+                names.sort();
+                names
+            }),
+            Kind::Uv => capture(user_command("uv").args(["tool", "list"])).map(|output| {
+                let mut names: Vec<String> = parse_uv_tool_list(&output).into_keys().collect();
+                names.sort();
+                names
+            }),
+            // npm and corepack come with Node.js.
+            Kind::Npm | Kind::Pnpm => global_node_modules(kind.program()).map(|dir| {
+                let mut names: Vec<String> = read_node_modules(&dir)
+                    .into_keys()
+                    .filter(|name| !["npm", "corepack"].contains(&name.as_str()))
+                    .collect();
+                names.sort();
+                names
+            }),
+            Kind::Go => {
+                let programs = go_bin_dir()
+                    .filter(|dir| dir.is_dir())
+                    .and_then(|dir| capture(user_command("go").arg("version").arg("-m").arg(dir)))
+                    .map(|output| parse_go_version(&output))
+                    .unwrap_or_default();
+                let mut programs: Vec<String> = programs.into_keys().collect();
+                programs.sort();
+                for package in programs {
+                    let name = go_binary(&package);
+                    packages.push(Package::new(name, kind.source(&package), guess_page(name)));
+                }
+                continue;
+            }
+            Kind::Rustup => {
+                let toolchains = capture(user_command("rustup").args(["toolchain", "list"]));
+                for toolchain in toolchains
+                    .map(|output| parse_toolchains(&output))
+                    .unwrap_or_default()
+                {
+                    let channel = channel(&toolchain);
+                    let source = kind.source(channel);
+                    packages.push(Package::new(
+                        &format!("Rust {channel}"),
+                        source,
+                        Page::Languages,
+                    ));
+                }
+                continue;
+            }
+        };
+
+        for name in names.unwrap_or_default() {
+            let page = guess_page(&name);
+            packages.push(Package::new(&name, kind.source(&name), page));
+        }
+    }
+
+    packages
+}
+
+/// Picks the page a package most likely belongs on from its name: libraries by
+/// their `lib` prefix or development suffix, shells, languages and editors by their
+/// name without version numbers, language servers with languages, and tools for
+/// everything else.
+pub fn guess_page(name: &str) -> Page {
+    let name = name.to_lowercase();
+
+    if name.starts_with("lib")
+        || ["-dev", "-devel", "-headers", "-static", "-doc"]
+            .iter()
+            .any(|suffix| name.ends_with(suffix))
+    {
+        return Page::Libraries;
+    }
+
+    if name.contains("language-server") {
+        return Page::Languages;
+    }
+
+    // `python3.12` and `openjdk-17-jdk` are matched as `python` and `openjdk`.
+    let stem: String = name.chars().take_while(char::is_ascii_alphabetic).collect();
+    let matches = |names: &[&str]| names.contains(&name.as_str()) || names.contains(&stem.as_str());
+
+    if matches(&SHELLS) {
+        Page::Shells
+    } else if matches(&LANGUAGES) {
+        Page::Languages
+    } else if matches(&EDITORS) {
+        Page::Editors
+    } else {
+        Page::Tools
+    }
+}
+
+/// Parses output listing one package name per line, such as that of
+/// `apt-mark showmanual` or `pacman -Qqe`, skipping anything that is not a name.
+pub fn parse_names(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.contains(char::is_whitespace))
+        .map(String::from)
+        .collect()
+}
+
+/// Parses `/var/lib/dpkg/status` for the packages of the base system, those with a
+/// `required` or `important` priority.
+pub fn parse_dpkg_base(status: &str) -> HashSet<String> {
+    status
+        .split("\n\n")
+        .filter_map(|stanza| {
+            let mut name = None;
+            let mut base = false;
+
+            for line in stanza.lines() {
+                if let Some(value) = line.strip_prefix("Package:") {
+                    name = Some(value.trim().to_string());
+                } else if let Some(value) = line.strip_prefix("Priority:") {
+                    base = matches!(value.trim(), "required" | "important");
+                }
+            }
+
+            base.then_some(name?)
+        })
+        .collect()
+}
+
+/// Parses the output of `flatpak list --app --columns=name,application` into pairs
+/// of application name and id.
+pub fn parse_flatpak_apps(output: &str) -> Vec<(String, String)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (name, id) = line.split_once('\t')?;
+            let id = id.trim();
+            let name = match name.trim() {
+                "" => id,
+                name => name,
+            };
+            (!id.is_empty()).then(|| (name.to_string(), id.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guess_page_by_name() {
+        let cases = [
+            ("python3.12", Page::Languages),
+            ("openjdk-17-jdk", Page::Languages),
+            ("rust-analyzer", Page::Languages),
+            ("typescript-language-server", Page::Languages),
+            ("bash-language-server", Page::Languages),
+            ("libfoo-dev", Page::Libraries),
+            ("libssl3", Page::Libraries),
+            ("openssl-devel", Page::Libraries),
+            ("fish", Page::Shells),
+            ("zsh-5.9", Page::Shells),
+            ("Neovim", Page::Editors),
+            ("ripgrep", Page::Tools),
+        ];
+        for (name, page) in cases {
+            assert_eq!(guess_page(name), page, "{name}");
+        }
+    }
+
+    #[test]
+    fn parse_apt_mark_showmanual() {
+        let output = "git\nneovim\n\nlibssl-dev\n";
+        assert_eq!(parse_names(output), ["git", "neovim", "libssl-dev"]);
+    }
+
+    #[test]
+    fn parse_pacman_qqe() {
+        let output = "base\nfish\n  rustup  \nerror: could not open database\n";
+        assert_eq!(parse_names(output), ["base", "fish", "rustup"]);
+    }
+
+    #[test]
+    fn dpkg_base_packages() {
+        let status = "\
+Package: bash
+Status: install ok installed
+Priority: required
+Version: 5.2.15-2
+
+Package: apt
+Priority: important
+Version: 2.6.1
+
+Package: git
+Priority: optional
+Version: 1:2.39.2-1.1
+
+Package: ripgrep
+Version: 13.0.0-4
+";
+        assert_eq!(
+            parse_dpkg_base(status),
+            HashSet::from(["bash".to_string(), "apt".to_string()])
+        );
+    }
+
+    #[test]
+    fn flatpak_apps() {
+        let output = "Firefox\torg.mozilla.firefox\n\tcom.example.Nameless\nBroken line\n\t\n";
+        assert_eq!(
+            parse_flatpak_apps(output),
+            [
+                ("Firefox".to_string(), "org.mozilla.firefox".to_string()),
+                (
+                    "com.example.Nameless".to_string(),
+                    "com.example.Nameless".to_string()
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,271 @@
+// SPDX-License-Identifier: GPL-3.0
+
+//! Fills in the `{{ variable }}` placeholders of templated config files.
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+use crate::app::models::blueprint::Variable;
+use crate::app::models::package::Package;
+
+use super::host::HostInfo;
+
+/// Values of variables by name.
+pub type Variables = BTreeMap<String, String>;
+
+/// Variables that always have a value, taken from the machine.
+pub const BUILTINS: [&str; 4] = ["user", "home", "hostname", "distro"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// A placeholder names a variable without a value.
+    Undefined(String),
+    /// A placeholder is not a valid variable name.
+    InvalidName(String),
+    /// A `{{` is never closed.
+    Unclosed,
+}
+
+/// The values of the built-in variables on `host`.
+pub fn builtins(host: &HostInfo) -> Variables {
+    let home = dirs::home_dir().unwrap_or_default();
+    let user = std::env::var("USER")
+        .ok()
+        .or_else(|| Some(home.file_name()?.to_string_lossy().into_owned()))
+        .unwrap_or_default();
+    let hostname = ["/proc/sys/kernel/hostname", "/etc/hostname"]
+        .iter()
+        .find_map(|path| std::fs::read_to_string(path).ok())
+        .map(|hostname| hostname.trim().to_string())
+        .unwrap_or_default();
+
+    BTreeMap::from([
+        ("user".to_string(), user),
+        ("home".to_string(), home.display().to_string()),
+        ("hostname".to_string(), hostname),
+        ("distro".to_string(), host.os.id.clone()),
+    ])
+}
+
+/// The value of every variable available to a blueprint declaring `declared`.
+///
+/// `values` given on this machine win over the defaults of the declarations,
+/// which win over the built-in variables. Empty values count as unset.
+pub fn resolve(host: &HostInfo, declared: &[Variable], values: &Variables) -> Variables {
+    let mut variables = builtins(host);
+
+    let defaults = declared
+        .iter()
+        .map(|variable| (&variable.name, &variable.default));
+    for (name, value) in defaults.chain(values) {
+        if !value.is_empty() {
+            variables.insert(name.clone(), value.clone());
+        }
+    }
+
+    variables
+}
+
+/// Replaces the placeholders of `template` with their values.
+///
+/// A placeholder is a variable name between `{{` and `}}`, such as `{{ email }}`.
+/// `\{{` is written out as a literal `{{`.
+pub fn render(template: &str, variables: &Variables) -> Result<String, Error> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        if rest[..start].ends_with('\\') {
+            rendered.push_str(&rest[..start - 1]);
+            rendered.push_str("{{");
+            rest = &rest[start + 2..];
+            continue;
+        }
+
+        rendered.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after.find("}}").ok_or(Error::Unclosed)?;
+        let name = parse_name(&after[..end])?;
+        let value = variables
+            .get(name)
+            .ok_or_else(|| Error::Undefined(name.to_string()))?;
+        rendered.push_str(value);
+        rest = &after[end + 2..];
+    }
+
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+/// The variables used by the templated config files of the enabled `packages` that
+/// have no value in `variables`, sorted by name.
+pub fn missing(packages: &[Package], variables: &Variables) -> Vec<String> {
+    let mut missing: Vec<String> = packages
+        .iter()
+        .filter(|package| package.enabled)
+        .flat_map(|package| &package.config)
+        .filter(|file| file.template)
+        .flat_map(|file| placeholders(&file.content))
+        .filter(|name| !variables.contains_key(*name))
+        .map(String::from)
+        .collect();
+
+    missing.sort();
+    missing.dedup();
+    missing
+}
+
+/// The names of the variables `template` uses, skipping malformed placeholders.
+pub fn placeholders(template: &str) -> Vec<&str> {
+    let mut names = vec![];
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        let escaped = rest[..start].ends_with('\\');
+        let after = &rest[start + 2..];
+        if escaped {
+            rest = after;
+            continue;
+        }
+
+        let Some(end) = after.find("}}") else {
+            break;
+        };
+        if let Ok(name) = parse_name(&after[..end]) {
+            names.push(name);
+        }
+        rest = &after[end + 2..];
+    }
+
+    names
+}
+
+fn parse_name(placeholder: &str) -> Result<&str, Error> {
+    let name = placeholder.trim();
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+
+    if valid {
+        Ok(name)
+    } else {
+        Err(Error::InvalidName(name.to_string()))
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Undefined(name) => write!(f, "variable `{name}` has no value"),
+            Error::InvalidName(name) => write!(f, "`{name}` is not a valid variable name"),
+            Error::Unclosed => write!(f, "a `{{{{` is not closed with `}}}}`"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::models::package::{ConfigFile, Source};
+    use crate::app::Page;
+
+    use super::*;
+
+    fn variables(pairs: &[(&str, &str)]) -> Variables {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn templated(content: &str) -> Package {
+        let mut package = Package::new("git", Source::Apt("git".into()), Page::Tools);
+        package.config.push(ConfigFile {
+            template: true,
+            ..ConfigFile::new("~/.gitconfig", content)
+        });
+        package
+    }
+
+    #[test]
+    fn render_ignores_whitespace_inside_braces() {
+        let variables = variables(&[("email", "me@example.com"), ("user", "me")]);
+        assert_eq!(
+            render("{{email}} {{ email }} {{\tuser  }}", &variables),
+            Ok("me@example.com me@example.com me".to_string())
+        );
+    }
+
+    #[test]
+    fn render_keeps_escaped_braces() {
+        let variables = variables(&[("user", "me")]);
+        assert_eq!(
+            render("\\{{ user }} is {{ user }}", &variables),
+            Ok("{{ user }} is me".to_string())
+        );
+    }
+
+    #[test]
+    fn render_errors() {
+        let variables = variables(&[("user", "me")]);
+        assert_eq!(
+            render("{{ email }}", &variables),
+            Err(Error::Undefined("email".into()))
+        );
+        assert_eq!(render("name = {{ user", &variables), Err(Error::Unclosed));
+        assert_eq!(
+            render("{{ user name }}", &variables),
+            Err(Error::InvalidName("user name".into()))
+        );
+        assert_eq!(
+            render("{{}}", &variables),
+            Err(Error::InvalidName("".into()))
+        );
+    }
+
+    #[test]
+    fn placeholders_skip_malformed_ones() {
+        assert_eq!(
+            placeholders("{{ name }} {{email}} \\{{ escaped }} {{ not valid }} {{ user"),
+            ["name", "email"]
+        );
+        assert!(placeholders("no placeholders } {").is_empty());
+    }
+
+    #[test]
+    fn missing_counts_builtins_and_declared_variables() {
+        let declared = [
+            Variable {
+                name: "editor".into(),
+                default: "vim".into(),
+                ..Default::default()
+            },
+            Variable {
+                name: "email".into(),
+                ..Default::default()
+            },
+        ];
+        let resolved = resolve(&HostInfo::default(), &declared, &Variables::new());
+        let packages = [templated(
+            "{{ user }} {{ home }} {{ editor }} {{ email }} {{ signing_key }} {{ email }}",
+        )];
+        assert_eq!(missing(&packages, &resolved), ["email", "signing_key"]);
+
+        // Values given on this machine fill in declared variables without a default.
+        let values = variables(&[("email", "me@example.com"), ("editor", "")]);
+        let resolved = resolve(&HostInfo::default(), &declared, &values);
+        assert_eq!(resolved["editor"], "vim");
+        assert_eq!(missing(&packages, &resolved), ["signing_key"]);
+    }
+
+    #[test]
+    fn missing_skips_disabled_packages_and_plain_files() {
+        let mut disabled = templated("{{ email }}");
+        disabled.enabled = false;
+        let mut plain = templated("{{ email }}");
+        plain.config[0].template = false;
+        assert!(missing(&[disabled, plain], &Variables::new()).is_empty());
+    }
+}
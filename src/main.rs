@@ -1,29 +1,46 @@
 // SPDX-License-Identifier: GPL-3.0
 
+use std::process::ExitCode;
+
+use clap::Parser;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 mod app;
+mod cli;
 mod config;
+mod engine;
 mod i18n;
 
-fn main() -> cosmic::iced::Result {
+fn main() -> ExitCode {
     // Get the system's preferred languages.
     let requested_languages = i18n_embed::DesktopLanguageRequester::requested_languages();
 
     // Enable localizations to be applied.
     i18n::init(&requested_languages);
 
+    // Logs go to stderr, since stdout is the channel the helper answers on.
     std::env::set_var("RUST_LOG", "blueprint=info");
     tracing_subscriber::registry()
-        .with(tracing_subscriber::fmt::layer())
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
         .with(tracing_subscriber::EnvFilter::from_default_env())
         .init();
 
+    // Run headless when a command is given on the command line.
+    let cli = cli::Cli::parse();
+    if cli.command.is_some() {
+        return cli::run(cli);
+    }
+
     // Settings for configuring the application window and iced runtime.
     let settings = cosmic::app::Settings::default();
 
     log::info!("Starting Blueprint...");
 
     // Starts the application's event loop with `()` as the application's flags.
-    cosmic::app::run::<app::AppModel>(settings, ())
+    if let Err(err) = cosmic::app::run::<app::AppModel>(settings, ()) {
+        log::error!("failed to run the application: {}", err);
+        return ExitCode::FAILURE;
+    }
+
+    ExitCode::SUCCESS
 }